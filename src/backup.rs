@@ -0,0 +1,466 @@
+//! Backup and restore of a whole instance.
+//!
+//! [`Qbit::backup`] snapshots preferences, categories, tags, RSS rules and
+//! every torrent with its state into a [`Backup`], which is stored as a
+//! single JSON document. [`Qbit::restore`] replays it onto another instance.
+//!
+//! ```rust,ignore
+//! let (backup, failures) = old.backup().await?;
+//! for failure in &failures {
+//!     eprintln!("Skipped {}: {}", failure.hashes, failure.error);
+//! }
+//! backup.write_to(std::fs::File::create("qbittorrent.json")?)?;
+//!
+//! let backup = Backup::read_from(std::fs::File::open("qbittorrent.json")?)?;
+//! new.restore(&backup, &RestoreOptions::default()).await?;
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{Read, Write},
+    time::Duration,
+};
+
+use futures_util::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_with::{base64::Base64, serde_as};
+use tracing::{debug, info, warn};
+
+use crate::{
+    batch::BatchFailure,
+    ext::ResponseExt,
+    migrate::{find, wait_for_torrent},
+    model::{
+        AddTorrentArg, Category, NonEmptyStr, Preferences, Priority, RssRule, Sep, State, Torrent,
+        TorrentSource,
+    },
+    ApiError, Error, Qbit, Result,
+};
+
+/// Snapshot of an instance, taken with [`Qbit::backup`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Backup {
+    /// Version of the backup format, see [`Backup::FORMAT_VERSION`]
+    pub format_version: u32,
+    /// Version of qBittorrent the backup was taken from
+    pub app_version: String,
+    pub preferences: Preferences,
+    pub categories: HashMap<String, Category>,
+    pub tags: Vec<String>,
+    pub rss_rules: HashMap<String, RssRule>,
+    pub torrents: Vec<TorrentBackup>,
+}
+
+/// A torrent and its state in a [`Backup`]
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TorrentBackup {
+    pub hash: String,
+    pub name: Option<String>,
+    /// Content of the .torrent file. `None` if the metadata had not been
+    /// downloaded yet, in which case the torrent is restored from
+    /// `magnet_uri`.
+    #[serde_as(as = "Option<Base64>")]
+    pub torrent: Option<Vec<u8>>,
+    pub magnet_uri: Option<String>,
+    pub save_path: Option<String>,
+    pub category: Option<String>,
+    pub tags: Vec<String>,
+    pub paused: bool,
+    pub auto_tmm: Option<bool>,
+    pub up_limit: Option<i64>,
+    pub dl_limit: Option<i64>,
+    pub ratio_limit: Option<f64>,
+    pub seeding_time_limit: Option<i64>,
+    pub sequential_download: Option<bool>,
+    pub first_last_piece_priority: Option<bool>,
+    /// Priorities of files not downloaded with [`Priority::Normal`], by file
+    /// index
+    pub file_priorities: BTreeMap<u64, Priority>,
+}
+
+impl Backup {
+    /// Version of the format written by this version of the library
+    pub const FORMAT_VERSION: u32 = 1;
+
+    pub fn write_to(&self, writer: impl Write) -> Result<()> {
+        serde_json::to_writer(writer, self).map_err(Into::into)
+    }
+
+    pub fn read_from(reader: impl Read) -> Result<Self> {
+        serde_json::from_reader(reader).map_err(Into::into)
+    }
+}
+
+/// What [`Qbit::restore`] replays
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestoreOptions {
+    /// Restore preferences
+    pub preferences: bool,
+    /// Also restore the WebUI preferences (`web_ui_*`), which may make the
+    /// instance unreachable with the current client
+    pub web_ui_preferences: bool,
+    /// Skip hash checking when adding torrents. Only safe if the files are
+    /// already in place.
+    pub skip_checking: bool,
+    /// How long to wait for each added torrent to appear before setting its
+    /// tags and file priorities
+    pub timeout: Duration,
+    /// Maximum number of torrents restored at the same time
+    pub concurrency: usize,
+}
+
+impl Default for RestoreOptions {
+    fn default() -> Self {
+        Self {
+            preferences: true,
+            web_ui_preferences: false,
+            skip_checking: false,
+            timeout: Duration::from_secs(30),
+            concurrency: 4,
+        }
+    }
+}
+
+impl Qbit {
+    /// Take a [`Backup`] of the instance.
+    ///
+    /// Torrents whose metadata has not been downloaded yet are saved with
+    /// their magnet link instead of the .torrent file. Torrents that fail to
+    /// back up do not stop the others: they are left out of the backup and
+    /// returned alongside it.
+    pub async fn backup(&self) -> Result<(Backup, Vec<BatchFailure>)> {
+        let torrents = self.get_torrent_list(Default::default()).await?;
+        let total = torrents.len();
+        debug!(count = total, "Backing up torrents");
+
+        let results = stream::iter(torrents)
+            .filter_map(|t| async move { t.hash.clone().map(|hash| (hash, t)) })
+            .map(|(hash, torrent)| async move {
+                self.backup_torrent(hash.clone(), torrent)
+                    .await
+                    .map_err(|error| BatchFailure {
+                        hashes: vec![hash].into(),
+                        error,
+                    })
+            })
+            .buffered(4)
+            .collect::<Vec<_>>()
+            .await;
+        let mut torrents = vec![];
+        let mut failures = vec![];
+        for result in results {
+            match result {
+                Ok(torrent) => torrents.push(torrent),
+                Err(failure) => failures.push(failure),
+            }
+        }
+        if !failures.is_empty() {
+            warn!(
+                total,
+                failed = failures.len(),
+                "Failed to back up some torrents"
+            );
+        }
+
+        let backup = Backup {
+            format_version: Backup::FORMAT_VERSION,
+            app_version: self.get_version().await?,
+            preferences: self.get_preferences().await?,
+            categories: self.get_categories().await?,
+            tags: self.get_all_tags().await?,
+            rss_rules: self.get_rss_rules().await?,
+            torrents,
+        };
+        Ok((backup, failures))
+    }
+
+    async fn backup_torrent(&self, hash: String, torrent: Torrent) -> Result<TorrentBackup> {
+        let data = match self.export_torrent(&hash).await {
+            Ok(data) => Some(data),
+            Err(Error::ApiError {
+                error: ApiError::MetadataNotDownloaded,
+                ..
+            }) => None,
+            Err(e) => return Err(e),
+        };
+        let file_priorities = match data {
+            Some(_) => self
+                .get_torrent_contents(&hash, None)
+                .await?
+                .into_iter()
+                .filter(|f| f.priority != Priority::Normal)
+                .map(|f| (f.index, f.priority))
+                .collect(),
+            None => BTreeMap::new(),
+        };
+
+        Ok(TorrentBackup {
+            torrent: data,
+            name: torrent.name,
+            magnet_uri: torrent.magnet_uri,
+            save_path: torrent.save_path,
+            category: torrent.category.filter(|c| !c.is_empty()),
+            tags: torrent
+                .tags
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(ToOwned::to_owned)
+                .collect(),
+            paused: torrent.state.as_ref().is_some_and(State::is_paused),
+            auto_tmm: torrent.auto_tmm,
+            up_limit: torrent.up_limit,
+            dl_limit: torrent.dl_limit,
+            ratio_limit: torrent.ratio_limit,
+            seeding_time_limit: torrent.seeding_time_limit,
+            sequential_download: torrent.seq_dl,
+            first_last_piece_priority: torrent.f_l_piece_prio,
+            file_priorities,
+            hash,
+        })
+    }
+
+    /// Replay `backup` onto this instance.
+    ///
+    /// Categories are created or updated, tags and RSS rules are created, and
+    /// torrents that do not exist yet are added with their state. Torrents
+    /// that fail to restore do not stop the others and are collected into
+    /// [`Error::BatchFailed`].
+    pub async fn restore(&self, backup: &Backup, options: &RestoreOptions) -> Result<()> {
+        if options.preferences {
+            self.set_preferences(restored_preferences(backup, options)?)
+                .await?;
+        }
+
+        let existing = self.get_categories().await?;
+        for (name, category) in &backup.categories {
+            let Some(name) = NonEmptyStr::new(name.as_str()) else {
+                continue;
+            };
+            if existing.contains_key(name.as_str()) {
+                self.edit_category(name, &category.save_path).await?;
+            } else {
+                self.add_category(name, &category.save_path).await?;
+            }
+        }
+        if !backup.tags.is_empty() {
+            self.create_tags(backup.tags.clone()).await?;
+        }
+        for (name, rule) in &backup.rss_rules {
+            self.set_rss_rule(name, rule).await?;
+        }
+
+        let total = backup.torrents.len();
+        let failures = stream::iter(&backup.torrents)
+            .map(|torrent| async move {
+                self.restore_torrent(torrent, options)
+                    .await
+                    .err()
+                    .map(|error| BatchFailure {
+                        hashes: vec![torrent.hash.clone()].into(),
+                        error,
+                    })
+            })
+            .buffer_unordered(options.concurrency.max(1))
+            .filter_map(|failure| async move { failure })
+            .collect::<Vec<_>>()
+            .await;
+
+        if failures.is_empty() {
+            info!(total, "Restored backup");
+            Ok(())
+        } else {
+            warn!(
+                total,
+                failed = failures.len(),
+                "Failed to restore some torrents"
+            );
+            Err(Error::BatchFailed { total, failures })
+        }
+    }
+
+    async fn restore_torrent(
+        &self,
+        torrent: &TorrentBackup,
+        options: &RestoreOptions,
+    ) -> Result<()> {
+        let hash = torrent.hash.as_str();
+        if find(self, hash).await?.is_some() {
+            debug!(hash, "Torrent already exists, skipping");
+            return Ok(());
+        }
+
+        let source = match (&torrent.torrent, &torrent.magnet_uri) {
+            (Some(data), _) => TorrentSource::TorrentFiles {
+                torrents: data.clone(),
+            },
+            (None, Some(magnet)) => TorrentSource::Urls {
+                urls: Sep::from(vec![magnet.parse().map_err(|_| Error::BadResponse {
+                    explain: "Invalid magnet link in backup",
+                    context: None,
+                })?]),
+            },
+            (None, None) => return Err(Error::from(ApiError::InvalidTorrent)),
+        };
+        self.add_torrent(AddTorrentArg {
+            source,
+            savepath: torrent.save_path.clone(),
+            category: torrent.category.clone(),
+            skip_checking: Some(options.skip_checking.to_string()),
+            paused: Some(torrent.paused.to_string()),
+            up_limit: torrent.up_limit,
+            download_limit: torrent.dl_limit,
+            ratio_limit: torrent.ratio_limit,
+            seeding_time_limit: torrent.seeding_time_limit,
+            auto_torrent_management: torrent.auto_tmm,
+            sequential_download: torrent.sequential_download.map(|b| b.to_string()),
+            first_last_piece_priority: torrent.first_last_piece_priority.map(|b| b.to_string()),
+            ..Default::default()
+        })
+        .await?;
+
+        wait_for_torrent(self, hash, options.timeout, Duration::from_millis(500)).await?;
+        if !torrent.tags.is_empty() {
+            self.add_torrent_tags(vec![hash.to_owned()], vec![torrent.tags.join(",")])
+                .await?;
+        }
+
+        let mut by_priority = BTreeMap::<u8, (Priority, Vec<i64>)>::new();
+        for (&index, &priority) in &torrent.file_priorities {
+            by_priority
+                .entry(priority as u8)
+                .or_insert_with(|| (priority, vec![]))
+                .1
+                .push(index as i64);
+        }
+        for (priority, indexes) in by_priority.into_values() {
+            self.set_file_priority(hash, indexes, priority).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// RSS endpoints, only needed to back up and restore the rules
+impl Qbit {
+    async fn get_rss_rules(&self) -> Result<HashMap<String, RssRule>> {
+        self.get("rss/rules").await?.check()?.parse_json().await
+    }
+
+    /// Create the RSS auto-downloading rule `name`, or replace it if it exists
+    async fn set_rss_rule(&self, name: &str, rule: &RssRule) -> Result<()> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Arg<'a> {
+            rule_name: &'a str,
+            rule_def: String,
+        }
+
+        self.post(
+            "rss/setRule",
+            Some(&Arg {
+                rule_name: name,
+                rule_def: serde_json::to_string(rule)?,
+            }),
+        )
+        .await?
+        .end()
+    }
+}
+
+/// Preferences to restore, without the WebUI ones unless requested
+fn restored_preferences(backup: &Backup, options: &RestoreOptions) -> Result<Preferences> {
+    if options.web_ui_preferences {
+        return Ok(backup.preferences.clone());
+    }
+    let mut value = serde_json::to_value(&backup.preferences)?;
+    if let Some(map) = value.as_object_mut() {
+        map.retain(|k, _| !k.starts_with("web_ui_") && k != "bypass_local_auth");
+    }
+    serde_json::from_value(value).map_err(Into::into)
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_backup_restore() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use crate::mock::{self, serve, MockResponse};
+
+    let torrent = r#"[{"hash":"abc","name":"a","category":"linux","tags":"x, y","state":"stoppedUP","save_path":"/data","up_limit":1024}]"#;
+    let torrents = r#"[{"hash":"abc","name":"a","category":"linux","tags":"x, y","state":"stoppedUP","save_path":"/data","up_limit":1024},
+                       {"hash":"bad","name":"b","state":"pausedDL"}]"#;
+    let (url, _) = serve(move |req| match req.path.split('?').next().unwrap() {
+        "/api/v2/app/version" => MockResponse::ok("v4.6.0"),
+        "/api/v2/app/preferences" => MockResponse::ok(r#"{"locale":"en","web_ui_port":8080}"#),
+        "/api/v2/torrents/categories" => {
+            MockResponse::ok(r#"{"linux":{"name":"linux","savePath":"/data/linux"}}"#)
+        }
+        "/api/v2/torrents/tags" => MockResponse::ok(r#"["x","y"]"#),
+        "/api/v2/rss/rules" => MockResponse::ok(r#"{"r":{"enabled":true,"torrentParams":{}}}"#),
+        "/api/v2/torrents/info" => MockResponse::ok(torrents),
+        "/api/v2/torrents/export" if req.path.ends_with("hash=bad") => MockResponse::status(500),
+        "/api/v2/torrents/export" => MockResponse::ok("d4:infoe"),
+        "/api/v2/torrents/files" => MockResponse::ok(
+            r#"[{"index":0,"name":"a","size":1,"progress":0,"priority":1},
+                {"index":1,"name":"b","size":1,"progress":0,"priority":0}]"#,
+        ),
+        _ => MockResponse::status(404),
+    })
+    .await;
+    let source = mock::client(url);
+
+    let (backup, failures) = source.backup().await.unwrap();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].hashes.to_string(), "bad");
+    assert_eq!(backup.torrents.len(), 1);
+    let mut file = vec![];
+    backup.write_to(&mut file).unwrap();
+    let backup = Backup::read_from(file.as_slice()).unwrap();
+    assert_eq!(backup.app_version, "v4.6.0");
+    assert_eq!(
+        backup.rss_rules["r"].extra["torrentParams"],
+        serde_json::json!({})
+    );
+    let saved = &backup.torrents[0];
+    assert_eq!(saved.torrent.as_deref(), Some(&b"d4:infoe"[..]));
+    assert_eq!(saved.tags, ["x", "y"]);
+    assert!(saved.paused);
+    assert_eq!(
+        saved.file_priorities,
+        BTreeMap::from([(1, Priority::DoNotDownload)])
+    );
+
+    let added = AtomicBool::new(false);
+    let (url, requests) = serve(move |req| match req.path.split('?').next().unwrap() {
+        "/api/v2/torrents/categories" => MockResponse::ok("{}"),
+        "/api/v2/torrents/info" if added.load(Ordering::SeqCst) => MockResponse::ok(torrent),
+        "/api/v2/torrents/info" => MockResponse::ok("[]"),
+        "/api/v2/torrents/add" => {
+            added.store(true, Ordering::SeqCst);
+            MockResponse::ok("Ok.")
+        }
+        _ => MockResponse::ok(""),
+    })
+    .await;
+    let target = mock::client(url);
+    target
+        .restore(&backup, &RestoreOptions::default())
+        .await
+        .unwrap();
+
+    let posted = |path: &str| requests.posted(&format!("/api/v2/{path}")).concat();
+    assert!(posted("app/setPreferences").contains("locale"));
+    assert!(!posted("app/setPreferences").contains("web_ui_port"));
+    assert_eq!(
+        posted("torrents/createCategory"),
+        "category=linux&savePath=%2Fdata%2Flinux"
+    );
+    assert_eq!(posted("torrents/createTags"), "tags=x%2Cy");
+    assert!(posted("rss/setRule").starts_with("ruleName=r&"));
+    assert!(posted("torrents/add").contains("d4:infoe"));
+    assert_eq!(posted("torrents/addTags"), "hashes=abc&tags=x%2Cy");
+    assert_eq!(posted("torrents/filePrio"), "hash=abc&id=1&priority=0");
+}
@@ -0,0 +1,136 @@
+//! Batching of hash-based operations.
+//!
+//! Endpoints taking [`Hashes`] send all of them in a single form body, which
+//! can exceed request size limits of reverse proxies when passing thousands of
+//! hashes. [`Qbit::batched`] splits them into chunks and runs the operation on
+//! each chunk with bounded concurrency.
+//!
+//! ```rust,ignore
+//! use qbit_rs::batch::BatchOptions;
+//!
+//! api.batched(hashes, BatchOptions::default(), |api, chunk| {
+//!     api.delete_torrents(chunk, false)
+//! })
+//! .await?;
+//! ```
+
+use std::future::Future;
+
+use futures_util::{stream, StreamExt};
+use tracing::{debug, warn};
+
+use crate::{model::Hashes, Error, Qbit, Result};
+
+/// How [`Qbit::batched`] splits and schedules the work
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchOptions {
+    /// Maximum number of hashes sent in one request
+    pub chunk_size: usize,
+    /// Maximum number of requests in flight
+    pub concurrency: usize,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: 200,
+            concurrency: 4,
+        }
+    }
+}
+
+/// A chunk that failed in a batched operation
+#[derive(Debug)]
+pub struct BatchFailure {
+    /// Hashes in the failed chunk
+    pub hashes: Hashes,
+    /// Error returned for the chunk
+    pub error: Error,
+}
+
+impl Qbit {
+    /// Run `op` on `hashes` split into chunks of at most
+    /// [`BatchOptions::chunk_size`], with at most
+    /// [`BatchOptions::concurrency`] chunks in flight.
+    ///
+    /// All chunks are attempted even if some of them fail. Failures are
+    /// collected into [`Error::BatchFailed`].
+    pub async fn batched<'a, F, Fut>(
+        &'a self,
+        hashes: impl Into<Hashes> + Send + Sync,
+        options: BatchOptions,
+        op: F,
+    ) -> Result<()>
+    where
+        F: Fn(&'a Qbit, Hashes) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<()>> + Send,
+    {
+        let chunks = hashes.into().chunks(options.chunk_size);
+        let total = chunks.len();
+        debug!(total, "Running batched operation");
+
+        let failures = stream::iter(chunks)
+            .map(|chunk| {
+                let fut = op(self, chunk.clone());
+                async move {
+                    fut.await.err().map(|error| BatchFailure {
+                        hashes: chunk,
+                        error,
+                    })
+                }
+            })
+            .buffer_unordered(options.concurrency.max(1))
+            .filter_map(|failure| async move { failure })
+            .collect::<Vec<_>>()
+            .await;
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            warn!(total, failed = failures.len(), "Batched operation failed");
+            Err(Error::BatchFailed { total, failures })
+        }
+    }
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_batched() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::{model::Credential, ApiError};
+
+    let api = Qbit::new("http://localhost:8080", Credential::dummy());
+    let hashes = (0..10).map(|i| i.to_string()).collect::<Vec<_>>();
+    let calls = AtomicUsize::new(0);
+
+    let res = api
+        .batched(
+            hashes,
+            BatchOptions {
+                chunk_size: 3,
+                concurrency: 2,
+            },
+            |_, chunk| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    if chunk.to_string().contains('9') {
+                        Err(Error::from(ApiError::TorrentNotFound))
+                    } else {
+                        Ok(())
+                    }
+                }
+            },
+        )
+        .await;
+
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+    match res {
+        Err(Error::BatchFailed { total, failures }) => {
+            assert_eq!(total, 4);
+            assert_eq!(failures.len(), 1);
+            assert_eq!(failures[0].hashes.to_string(), "9");
+        }
+        _ => panic!("Expected batch failure"),
+    }
+}
@@ -0,0 +1,246 @@
+//! Browsing directories on the machine running qBittorrent, e.g. to let users
+//! pick a save path.
+//!
+//! Paths picked this way can be submitted with [`Qbit::add_torrent_checked`]
+//! and [`Qbit::set_torrent_location_checked`], which fail early with
+//! [`ApiError::DirectoryNotFound`] if the directory does not exist on the
+//! server.
+//!
+//! ```rust,ignore
+//! let listing = api.browse("/data", false).await?;
+//! for dir in &listing.dirs {
+//!     println!("{}", dir.display());
+//! }
+//! api.set_torrent_location_checked(vec![hash], &listing.dirs[0]).await?;
+//! ```
+//!
+//! [`ApiError::DirectoryNotFound`]: crate::ApiError::DirectoryNotFound
+
+use std::{
+    borrow::Borrow,
+    path::{Path, PathBuf},
+};
+
+use tracing::debug;
+
+use crate::{
+    model::{AddTorrentArg, DirectoryContentMode, Hashes},
+    ApiError, Error, Qbit, Result,
+};
+
+/// Content of a directory, returned by [`Qbit::browse`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryListing {
+    /// The listed directory
+    pub path: PathBuf,
+    /// Parent of the listed directory, `None` at a root. Computed the way the
+    /// server sees the path: both `/` and `\` separate components, whatever
+    /// the platform of the client.
+    pub parent: Option<PathBuf>,
+    /// Subdirectories, sorted
+    pub dirs: Vec<PathBuf>,
+    /// Files, sorted. Only listed if requested.
+    pub files: Vec<PathBuf>,
+}
+
+impl Qbit {
+    /// List the subdirectories of `path`, and its files if `include_files`
+    pub async fn browse(
+        &self,
+        path: impl AsRef<Path> + Send + Sync,
+        include_files: bool,
+    ) -> Result<DirectoryListing> {
+        let path = path.as_ref();
+        let mut dirs = self
+            .get_directory_content(path, DirectoryContentMode::Dirs)
+            .await?;
+        dirs.sort();
+        let mut files = if include_files {
+            self.get_directory_content(path, DirectoryContentMode::Files)
+                .await?
+        } else {
+            vec![]
+        };
+        files.sort();
+
+        Ok(DirectoryListing {
+            path: path.to_owned(),
+            parent: remote_parent(&path.to_string_lossy()).map(PathBuf::from),
+            dirs,
+            files,
+        })
+    }
+
+    /// Check that the directory `path` exists on the server, returning
+    /// [`ApiError::DirectoryNotFound`] or [`ApiError::InvalidDirectoryPath`]
+    /// otherwise
+    ///
+    /// Only the subdirectories of the parent of `path` are listed, not the
+    /// content of `path` itself, which may be large. A root is listed
+    /// directly.
+    ///
+    /// [`ApiError::DirectoryNotFound`]: crate::ApiError::DirectoryNotFound
+    /// [`ApiError::InvalidDirectoryPath`]: crate::ApiError::InvalidDirectoryPath
+    pub async fn check_directory(&self, path: impl AsRef<Path> + Send + Sync) -> Result<()> {
+        let path = path.as_ref().to_string_lossy();
+        let exists = match remote_parent(&path) {
+            Some(parent) => self
+                .get_directory_content(parent, DirectoryContentMode::Dirs)
+                .await?
+                .iter()
+                .any(|dir| same_path(&dir.to_string_lossy(), &path)),
+            None => {
+                self.get_directory_content(path.as_ref(), DirectoryContentMode::Dirs)
+                    .await?;
+                true
+            }
+        };
+        if !exists {
+            return Err(Error::from(ApiError::DirectoryNotFound));
+        }
+        debug!(%path, "Directory exists");
+        Ok(())
+    }
+
+    /// [`add_torrent`](Self::add_torrent), after checking that
+    /// [`AddTorrentArg::savepath`] exists. An empty or missing save path uses
+    /// the default one and is not checked.
+    pub async fn add_torrent_checked(
+        &self,
+        arg: impl Borrow<AddTorrentArg> + Send + Sync,
+    ) -> Result<()> {
+        let arg = arg.borrow();
+        if let Some(savepath) = arg.savepath.as_deref().filter(|p| !p.is_empty()) {
+            self.check_directory(savepath).await?;
+        }
+        self.add_torrent(arg).await
+    }
+
+    /// [`set_torrent_location`](Self::set_torrent_location), after checking
+    /// that `location` exists
+    pub async fn set_torrent_location_checked(
+        &self,
+        hashes: impl Into<Hashes> + Send + Sync,
+        location: impl AsRef<Path> + Send + Sync,
+    ) -> Result<()> {
+        let location = location.as_ref();
+        self.check_directory(location).await?;
+        self.set_torrent_location(hashes, location).await
+    }
+}
+
+fn is_separator(c: char) -> bool {
+    c == '/' || c == '\\'
+}
+
+/// Parent of `path` on the server, which may use `/` or `\` as separator.
+/// `None` at a root such as `/` or `C:\`, or for a single relative component.
+fn remote_parent(path: &str) -> Option<&str> {
+    let path = path.trim_end_matches(is_separator);
+    let parent = path[..path.rfind(is_separator)?].trim_end_matches(is_separator);
+    let is_drive = parent.len() == 2
+        && parent.as_bytes()[0].is_ascii_alphabetic()
+        && parent.as_bytes()[1] == b':';
+    if parent.is_empty() || is_drive {
+        // Keep the separator of the root
+        Some(&path[..parent.len() + 1])
+    } else {
+        Some(parent)
+    }
+}
+
+/// Whether `a` and `b` are the same path on the server, up to separators
+fn same_path(a: &str, b: &str) -> bool {
+    let normalize = |p: &str| p.trim_end_matches(is_separator).replace('\\', "/");
+    normalize(a) == normalize(b)
+}
+
+#[test]
+fn test_remote_parent() {
+    assert_eq!(remote_parent("/data/tv/"), Some("/data"));
+    assert_eq!(remote_parent("/data"), Some("/"));
+    assert_eq!(remote_parent("/"), None);
+    assert_eq!(remote_parent("C:\\Users\\me"), Some("C:\\Users"));
+    assert_eq!(remote_parent("C:\\Users"), Some("C:\\"));
+    assert_eq!(remote_parent("D:/media"), Some("D:/"));
+    assert_eq!(remote_parent("C:\\"), None);
+    assert_eq!(remote_parent("data"), None);
+    assert!(same_path("C:/Users/", "C:\\Users"));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_browse() {
+    use crate::{
+        mock::{self, serve, MockResponse},
+        ApiError, Error,
+    };
+
+    let (url, requests) = serve(|req| match req.path.as_str() {
+        "/api/v2/app/getDirectoryContent?dirPath=%2Fdata&mode=dirs" => {
+            MockResponse::ok(r#"["/data/tv","/data/movies"]"#)
+        }
+        "/api/v2/app/getDirectoryContent?dirPath=%2Fdata&mode=files" => {
+            MockResponse::ok(r#"["/data/notes.txt"]"#)
+        }
+        "/api/v2/app/getDirectoryContent?dirPath=%2F&mode=dirs" => {
+            MockResponse::ok(r#"["/data","/home"]"#)
+        }
+        p if p.starts_with("/api/v2/app/getDirectoryContent") => MockResponse::status(404),
+        _ => MockResponse::ok(""),
+    })
+    .await;
+    let api = mock::client(url);
+
+    assert_eq!(
+        api.browse("/data", true).await.unwrap(),
+        DirectoryListing {
+            path: "/data".into(),
+            parent: Some("/".into()),
+            dirs: vec!["/data/movies".into(), "/data/tv".into()],
+            files: vec!["/data/notes.txt".into()],
+        }
+    );
+
+    assert!(matches!(
+        api.set_torrent_location_checked(vec!["abc".to_owned()], "/missing")
+            .await,
+        Err(Error::ApiError {
+            error: ApiError::DirectoryNotFound,
+            ..
+        })
+    ));
+    api.set_torrent_location_checked(vec!["abc".to_owned()], "/data")
+        .await
+        .unwrap();
+    let add = |savepath: &str| AddTorrentArg {
+        source: crate::model::TorrentSource::Urls {
+            urls: vec!["magnet:?xt=urn:btih:abc".parse().unwrap()].into(),
+        },
+        savepath: Some(savepath.to_owned()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        api.add_torrent_checked(add("/missing")).await,
+        Err(Error::ApiError {
+            error: ApiError::DirectoryNotFound,
+            ..
+        })
+    ));
+    api.add_torrent_checked(add("/data")).await.unwrap();
+
+    assert_eq!(
+        requests.posted_paths(),
+        ["/api/v2/torrents/setLocation", "/api/v2/torrents/add"]
+    );
+    // Checks only list the parent
+    let paths = requests.paths();
+    let listed = |dir: &str| {
+        paths
+            .iter()
+            .filter(|p| p.contains(&format!("dirPath={dir}&")))
+            .count()
+    };
+    assert_eq!(listed("%2Fmissing"), 0);
+    assert_eq!(listed("%2F"), 4);
+}
@@ -1,17 +1,32 @@
 #![allow(private_interfaces, private_bounds)]
 
-use std::{fmt::Debug, sync::Mutex};
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
-use reqwest::Client;
+use reqwest::{
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+    Client, RequestBuilder,
+};
 use tap::Pipe;
 use url::Url;
 
-use crate::{ext::Cookie, model::Credential, LoginState, Qbit};
+use crate::{
+    ext::{ApiKey, Cookie},
+    limit::{Limiter, RateLimit},
+    model::Credential,
+    retry::RetryPolicy,
+    session::SessionStore,
+    Config, Error, LoginState, Qbit, Result,
+};
 
 pub struct QbitBuilder<C = (), R = (), E = ()> {
     credential: C,
     client: R,
     endpoint: E,
+    config: Config,
 }
 
 trait IntoLoginState {
@@ -24,6 +39,15 @@ impl IntoLoginState for Cookie {
     }
 }
 
+impl IntoLoginState for ApiKey {
+    fn into_login_state(self) -> LoginState {
+        LoginState::ApiKeyProvided {
+            header: self.header,
+            value: self.value,
+        }
+    }
+}
+
 impl IntoLoginState for Credential {
     fn into_login_state(self) -> LoginState {
         LoginState::NotLoggedIn { credential: self }
@@ -36,6 +60,7 @@ impl QbitBuilder {
             credential: (),
             client: (),
             endpoint: (),
+            config: Config::default(),
         }
     }
 }
@@ -52,6 +77,7 @@ impl<C, R, E> QbitBuilder<C, R, E> {
             credential: self.credential,
             client,
             endpoint: self.endpoint,
+            config: self.config,
         }
     }
 
@@ -61,6 +87,34 @@ impl<C, R, E> QbitBuilder<C, R, E> {
             credential: Cookie(cookie.into()),
             client: self.client,
             endpoint: self.endpoint,
+            config: self.config,
+        }
+    }
+
+    /// Authenticate with an API key sent as `Authorization: Bearer <key>`
+    /// instead of logging in
+    pub fn api_key(self, key: impl AsRef<str>) -> QbitBuilder<ApiKey, R, E> {
+        self.api_key_header(
+            header::AUTHORIZATION.as_str(),
+            format!("Bearer {}", key.as_ref()),
+        )
+    }
+
+    /// Authenticate by sending `value` in the `header` header instead of
+    /// logging in
+    pub fn api_key_header(
+        self,
+        header: impl Into<String>,
+        value: impl Into<String>,
+    ) -> QbitBuilder<ApiKey, R, E> {
+        QbitBuilder {
+            credential: ApiKey {
+                header: header.into(),
+                value: value.into(),
+            },
+            client: self.client,
+            endpoint: self.endpoint,
+            config: self.config,
         }
     }
 
@@ -69,9 +123,121 @@ impl<C, R, E> QbitBuilder<C, R, E> {
             credential,
             client: self.client,
             endpoint: self.endpoint,
+            config: self.config,
         }
     }
 
+    /// Enable dry-run mode. Requests sent with `POST` are recorded as
+    /// [`PlannedAction`](crate::PlannedAction)s instead of being sent, while
+    /// `GET` requests still execute normally.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.config.dry_run = dry_run;
+        self
+    }
+
+    /// Set how failed requests are retried. Defaults to
+    /// [`RetryPolicy::default`].
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.config.retry = retry;
+        self
+    }
+
+    /// Limit the rate and concurrency of requests sent to the server
+    pub fn rate_limit(mut self, limit: RateLimit) -> Self {
+        self.config.rate_limit = limit;
+        self
+    }
+
+    /// Call `hook` with the new session cookie after each successful login,
+    /// e.g. to persist it.
+    pub fn on_cookie_change(mut self, hook: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.config.on_cookie_change = Some(Arc::new(hook));
+        self
+    }
+
+    /// Persist session cookies in `store`, so that they survive the
+    /// [`Qbit`] instance. Only used when logging in with a [`Credential`].
+    pub fn session_store(mut self, store: impl SessionStore + 'static) -> Self {
+        self.config.session_store = Some(Arc::new(store));
+        self
+    }
+
+    /// Mirror session cookies into `jar` after each login, e.g. to share the
+    /// session with other clients. If no [`Client`] is provided, the default
+    /// one also uses `jar` as its cookie store.
+    #[cfg(feature = "cookies")]
+    #[cfg_attr(feature = "docs", doc(cfg(feature = "cookies")))]
+    pub fn cookie_jar(mut self, jar: Arc<reqwest::cookie::Jar>) -> Self {
+        self.config.cookie_jar = Some(jar);
+        self
+    }
+
+    /// Call `hook` on every request before it is sent, including login, e.g.
+    /// to add headers required by a reverse proxy in front of qBittorrent
+    pub fn auth_hook(
+        mut self,
+        hook: impl Fn(RequestBuilder) -> RequestBuilder + Send + Sync + 'static,
+    ) -> Self {
+        self.config.auth_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Send `value` in the `name` header with every request, including login
+    pub fn default_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.config.headers.insert(name, value);
+        self
+    }
+
+    /// Send `headers` with every request, including login. Extends the
+    /// headers set with [`default_header`](Self::default_header).
+    pub fn default_headers(mut self, headers: HeaderMap) -> Self {
+        self.config.headers.extend(headers);
+        self
+    }
+
+    /// Authenticate to a reverse proxy in front of qBittorrent with HTTP basic
+    /// auth. Conflicts with [`api_key`](Self::api_key), since both use the
+    /// `Authorization` header: building then fails with
+    /// [`Error::ConflictingAuth`].
+    pub fn basic_auth(
+        mut self,
+        username: impl Into<String>,
+        password: Option<impl Into<String>>,
+    ) -> Self {
+        self.config.basic_auth = Some((username.into(), password.map(Into::into)));
+        self
+    }
+
+    /// Send `Origin` and `Referer` headers derived from the endpoint, so that
+    /// servers with `web_ui_csrf_protection_enabled` accept requests passing
+    /// through a reverse proxy. Combine with an `X-Forwarded-Host` default
+    /// header if the proxy rewrites `Host`.
+    pub fn origin_headers(mut self, enabled: bool) -> Self {
+        self.config.origin_headers = enabled;
+        self
+    }
+
+    /// Connect to the WebUI through the Unix domain socket at `path` instead
+    /// of TCP. The endpoint's host is then only used for the `Host` header.
+    ///
+    /// Only applies to the default client: configure a [`Client`] passed to
+    /// [`client`](Self::client) with
+    /// [`ClientBuilder::unix_socket`](reqwest::ClientBuilder::unix_socket)
+    /// instead.
+    #[cfg(unix)]
+    #[cfg_attr(feature = "docs", doc(cfg(unix)))]
+    pub fn unix_socket(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.config.unix_socket = Some(path.into());
+        self
+    }
+
+    /// Set a timeout applied to each request, overriding the one configured
+    /// on the [`Client`], if any.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = Some(timeout);
+        self
+    }
+
     pub fn endpoint<U>(self, endpoint: U) -> QbitBuilder<C, R, U>
     where
         U: TryInto<Url>,
@@ -80,6 +246,7 @@ impl<C, R, E> QbitBuilder<C, R, E> {
             credential: self.credential,
             client: self.client,
             endpoint,
+            config: self.config,
         }
     }
 }
@@ -90,15 +257,51 @@ where
     U: TryInto<Url>,
     U::Error: Debug,
 {
-    pub fn build(self) -> Qbit {
-        let endpoint = self.endpoint.try_into().expect("Invalid endpoint");
-        let state = self.credential.into_login_state().pipe(Mutex::new);
+    /// Build the [`Qbit`] instance, or return [`Error::InvalidEndpoint`] if
+    /// the endpoint cannot be parsed or is not an HTTP(S) URL, and
+    /// [`Error::ConflictingAuth`] if basic auth is combined with an API key in
+    /// the `Authorization` header.
+    pub fn try_build(self) -> Result<Qbit> {
+        let endpoint = self
+            .endpoint
+            .try_into()
+            .map_err(|e| Error::InvalidEndpoint(format!("{e:?}")))?
+            .pipe(normalize_endpoint);
+        if !matches!(endpoint.scheme(), "http" | "https") || endpoint.cannot_be_a_base() {
+            return Err(Error::InvalidEndpoint(format!(
+                "`{endpoint}` is not an HTTP(S) URL"
+            )));
+        }
+
+        let state = self.credential.into_login_state();
+        if let LoginState::ApiKeyProvided { header, .. } = &state {
+            if self.config.basic_auth.is_some()
+                && header.eq_ignore_ascii_case(header::AUTHORIZATION.as_str())
+            {
+                return Err(Error::ConflictingAuth);
+            }
+        }
+        let state = tokio::sync::Mutex::new(state);
 
-        Qbit {
+        Ok(Qbit {
             client: self.client,
             endpoint,
             state,
-        }
+            limiter: Limiter::new(self.config.rate_limit),
+            config: self.config,
+            planned: Mutex::new(vec![]),
+        })
+    }
+
+    /// Build the [`Qbit`] instance.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the endpoint is invalid or the authentication methods
+    /// conflict. Use [`try_build`](Self::try_build) to handle the error
+    /// instead.
+    pub fn build(self) -> Qbit {
+        self.try_build().expect("Failed to build Qbit")
     }
 }
 
@@ -108,9 +311,48 @@ where
     U: TryInto<Url>,
     U::Error: Debug,
 {
+    /// Build the [`Qbit`] instance with a default [`Client`], or return an
+    /// error if the endpoint is invalid, the authentication methods conflict
+    /// or the client cannot be built.
+    pub fn try_build(self) -> Result<Qbit> {
+        let builder = reqwest::Client::builder();
+        #[cfg(feature = "cookies")]
+        let builder = match self.config.cookie_jar.clone() {
+            Some(jar) => builder.cookie_provider(jar),
+            None => builder,
+        };
+        #[cfg(unix)]
+        let builder = match self.config.unix_socket.clone() {
+            Some(path) => builder.unix_socket(path),
+            None => builder,
+        };
+
+        self.client(builder.build()?).try_build()
+    }
+
+    /// Build the [`Qbit`] instance with a default [`Client`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the endpoint is invalid, the authentication methods conflict
+    /// or the client cannot be built. Use [`try_build`](Self::try_build) to
+    /// handle the error instead.
     pub fn build(self) -> Qbit {
-        self.client(reqwest::Client::new()).build()
+        self.try_build().expect("Failed to build Qbit")
+    }
+}
+
+/// Make sure the endpoint's path ends with a slash, so that the API path is
+/// joined after it instead of replacing its last segment, e.g.
+/// `https://host/qbittorrent` becomes `https://host/qbittorrent/`
+fn normalize_endpoint(mut endpoint: Url) -> Url {
+    if !endpoint.path().ends_with('/') {
+        let path = format!("{}/", endpoint.path());
+        endpoint.set_path(&path);
     }
+    endpoint.set_query(None);
+    endpoint.set_fragment(None);
+    endpoint
 }
 
 #[test]
@@ -136,4 +378,42 @@ fn test_builder() {
         .endpoint("http://localhost:8080")
         .cookie("SID=1234567890")
         .build();
+
+    QbitBuilder::new()
+        .endpoint("http://localhost:8080")
+        .credential(Credential::new("admin", "adminadmin"))
+        .retry_policy(RetryPolicy::none())
+        .rate_limit(RateLimit::new().requests_per_second(5).max_in_flight(1))
+        .timeout(Duration::from_secs(5))
+        .build();
+
+    let api = QbitBuilder::new()
+        .endpoint("https://example.com/qbittorrent?x=1")
+        .credential(Credential::dummy())
+        .build();
+    assert_eq!(
+        api.url("app/version").unwrap().as_str(),
+        "https://example.com/qbittorrent/api/v2/app/version"
+    );
+
+    for endpoint in ["not a url", "ftp://example.com", "data:text/plain,qbit"] {
+        let res = QbitBuilder::new()
+            .endpoint(endpoint)
+            .credential(Credential::dummy())
+            .try_build();
+        assert!(matches!(res, Err(Error::InvalidEndpoint(_))), "{endpoint}");
+    }
+
+    let res = QbitBuilder::new()
+        .endpoint("http://localhost:8080")
+        .api_key("key")
+        .basic_auth("proxy", Some("secret"))
+        .try_build();
+    assert!(matches!(res, Err(Error::ConflictingAuth)));
+
+    QbitBuilder::new()
+        .endpoint("http://localhost:8080")
+        .api_key_header("X-Api-Key", "key")
+        .basic_auth("proxy", Some("secret"))
+        .build();
 }
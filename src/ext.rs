@@ -1,7 +1,9 @@
-use reqwest::{header::SET_COOKIE, Response, StatusCode};
-use tap::Pipe;
+use std::time::{Duration, SystemTime};
 
-use crate::{ApiError, Error, Result};
+use reqwest::{header::SET_COOKIE, Method, Response, StatusCode};
+use serde::de::DeserializeOwned;
+
+use crate::{ApiError, Error, ErrorContext, Result};
 
 pub trait FromResponse {
     fn from_response(response: &Response) -> Result<Self>
@@ -9,20 +11,110 @@ pub trait FromResponse {
         Self: Sized;
 }
 
+/// Value of the `Cookie` header sent with each request
 pub struct Cookie(pub String);
 
-impl FromResponse for Cookie {
+/// Header carrying an API key, sent with each request
+pub struct ApiKey {
+    pub header: String,
+    pub value: String,
+}
+
+/// A cookie parsed from a `Set-Cookie` header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetCookie {
+    pub name: String,
+    pub value: String,
+    /// Absolute expiry computed from `Max-Age` or `Expires`. `None` for
+    /// session cookies.
+    pub expires: Option<SystemTime>,
+    /// The header as received
+    pub raw: String,
+}
+
+impl SetCookie {
+    pub fn parse(raw: &str) -> Option<Self> {
+        let mut parts = raw.split(';').map(str::trim);
+        let (name, value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut max_age = None;
+        let mut expires = None;
+        for attr in parts {
+            let (key, val) = attr.split_once('=').unwrap_or((attr, ""));
+            if key.eq_ignore_ascii_case("max-age") {
+                max_age = val.trim().parse::<i64>().ok();
+            } else if key.eq_ignore_ascii_case("expires") {
+                expires = httpdate::parse_http_date(val.trim()).ok();
+            }
+        }
+        // `Max-Age` takes precedence over `Expires`. One too large to be
+        // represented never expires.
+        let expires = match max_age {
+            Some(secs) => SystemTime::now().checked_add(Duration::from_secs(secs.max(0) as u64)),
+            None => expires,
+        };
+
+        Some(Self {
+            name: name.to_owned(),
+            value: value.trim().trim_matches('"').to_owned(),
+            expires,
+            raw: raw.to_owned(),
+        })
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires.is_some_and(|t| t <= SystemTime::now())
+    }
+}
+
+/// Cookies set by a login response
+pub struct SessionCookies {
+    pub cookies: Vec<SetCookie>,
+}
+
+impl SessionCookies {
+    /// Value of the `Cookie` header to send them back
+    pub fn header(&self) -> String {
+        self.cookies
+            .iter()
+            .map(|c| format!("{}={}", c.name, c.value))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    /// The earliest expiry among the cookies
+    pub fn expires(&self) -> Option<SystemTime> {
+        self.cookies.iter().filter_map(|c| c.expires).min()
+    }
+}
+
+impl FromResponse for SessionCookies {
     fn from_response(response: &Response) -> Result<Self> {
-        let cookie = response
-            .headers()
-            .get(SET_COOKIE)
-            .ok_or(Error::BadResponse {
+        let mut cookies = Vec::<SetCookie>::new();
+        for header in response.headers().get_all(SET_COOKIE) {
+            let header = header.to_str().map_err(|_| Error::NonAsciiHeader)?;
+            let Some(cookie) = SetCookie::parse(header) else {
+                continue;
+            };
+            // Later cookies with the same name replace earlier ones, and
+            // expired ones delete them
+            cookies.retain(|c| c.name != cookie.name);
+            if !cookie.is_expired() {
+                cookies.push(cookie);
+            }
+        }
+
+        if cookies.is_empty() {
+            return Err(Error::BadResponse {
                 explain: "Failed to extract cookie from response",
-            })?
-            .to_str()
-            .map_err(|_| Error::NonAsciiHeader)?
-            .to_owned();
-        Ok(Self(cookie))
+                context: response.error_context(),
+            });
+        }
+        Ok(Self { cookies })
     }
 }
 
@@ -37,7 +129,19 @@ pub trait ResponseExt: Sized {
 
     fn map_status<F: FnOnce(StatusCode) -> Option<Error>>(self, f: F) -> Result<Self>;
 
+    /// Return an error if the response is not successful
+    fn check(self) -> Result<Self> {
+        self.map_status(|_| None)
+    }
+
     fn end<T: FromResponse>(self) -> Result<T>;
+
+    /// Deserialize the JSON body, reporting the start of the body in the
+    /// error if it does not match `T`
+    async fn parse_json<T: DeserializeOwned>(self) -> Result<T>;
+
+    /// The request this response answers, attached by [`with_context`]
+    fn error_context(&self) -> Option<Box<ErrorContext>>;
 }
 
 impl ResponseExt for Response {
@@ -51,27 +155,109 @@ impl ResponseExt for Response {
         if status.is_success() {
             Ok(self)
         } else {
+            let context = self.error_context();
             match f(status) {
-                Some(err) => Err(err),
+                Some(err) => Err(err.with_context(context)),
                 None => match status {
-                    StatusCode::FORBIDDEN => Err(Error::ApiError(ApiError::NotLoggedIn)),
-                    _ => Ok(self),
+                    StatusCode::FORBIDDEN => {
+                        Err(Error::from(ApiError::NotLoggedIn).with_context(context))
+                    }
+                    _ => Err(Error::UnknownHttpCode { status, context }),
                 },
             }
         }
     }
 
     fn end<T: FromResponse>(self) -> Result<T> {
-        self.map_status(|c| Error::UnknownHttpCode(c).pipe(Some))
-            .and_then(|b| T::from_response(&b).map_err(Into::into))
+        self.check().and_then(|b| T::from_response(&b))
+    }
+
+    async fn parse_json<T: DeserializeOwned>(self) -> Result<T> {
+        let context = self.error_context();
+        let body = match self.bytes().await {
+            Ok(body) => body,
+            Err(e) => return Err(Error::from(e).with_context(context)),
+        };
+        serde_json::from_slice(&body)
+            .map_err(|e| Error::from(e).with_context(context.map(|c| Box::new(c.with_body(&body)))))
+    }
+
+    fn error_context(&self) -> Option<Box<ErrorContext>> {
+        self.extensions()
+            .get::<ErrorContext>()
+            .cloned()
+            .map(Box::new)
     }
 }
 
+/// Attach the request's method and path to `response`, and the start of the
+/// body if it is not successful, so that errors about it can report them.
+pub async fn with_context(
+    response: Response,
+    method: Method,
+    path: &'static str,
+) -> Result<Response> {
+    let status = response.status();
+    let mut context = ErrorContext {
+        method,
+        path,
+        status: Some(status),
+        body: None,
+    };
+
+    let mut response = if status.is_success() {
+        response
+    } else {
+        let headers = response.headers().clone();
+        let body = match response.bytes().await {
+            Ok(body) => body,
+            Err(e) => return Err(Error::from(e).with_context(Some(Box::new(context)))),
+        };
+        context = context.with_body(&body);
+
+        let mut buffered = http::Response::new(body);
+        *buffered.status_mut() = status;
+        *buffered.headers_mut() = headers;
+        Response::from(buffered)
+    };
+    response.extensions_mut().insert(context);
+
+    Ok(response)
+}
+
 /// Handle 404 returned by APIs with torrent hash as a parameter
 pub const TORRENT_NOT_FOUND: fn(StatusCode) -> Option<Error> = |s| {
     if s == StatusCode::NOT_FOUND {
-        Some(Error::ApiError(ApiError::TorrentNotFound))
+        Some(Error::from(ApiError::TorrentNotFound))
     } else {
         None
     }
 };
+
+#[test]
+fn test_session_cookies() {
+    let response: Response = http::Response::builder()
+        .header(
+            SET_COOKIE,
+            "QBT_SID_8080=abc; HttpOnly; SameSite=Strict; path=/",
+        )
+        .header(SET_COOKIE, "other=1; Max-Age=3600")
+        .header(SET_COOKIE, "stale=1; Expires=Thu, 01 Jan 1970 00:00:00 GMT")
+        .header(SET_COOKIE, "forever=1; Max-Age=9223372036854775807")
+        .body(Vec::<u8>::new())
+        .unwrap()
+        .into();
+
+    let cookies = response.extract::<SessionCookies>().unwrap();
+    assert_eq!(cookies.header(), "QBT_SID_8080=abc; other=1; forever=1");
+    assert_eq!(cookies.cookies[0].name, "QBT_SID_8080");
+    assert_eq!(cookies.cookies[0].expires, None);
+    assert_eq!(cookies.cookies[2].expires, None);
+
+    let expires = cookies.expires().unwrap();
+    assert!(expires > SystemTime::now() + Duration::from_secs(3500));
+    assert!(expires <= SystemTime::now() + Duration::from_secs(3600));
+
+    let empty: Response = http::Response::new(Vec::<u8>::new()).into();
+    assert!(empty.extract::<SessionCookies>().is_err());
+}
@@ -1,6 +1,5 @@
 #![doc = include_str!("../README.md")]
 #![warn(clippy::future_not_send)]
-#![cfg_attr(test, feature(lazy_cell))]
 #![cfg_attr(feature = "docs", feature(doc_cfg))]
 
 use std::{
@@ -8,33 +7,65 @@ use std::{
     collections::HashMap,
     fmt::Debug,
     path::{Path, PathBuf},
-    sync::{Mutex, MutexGuard},
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, SystemTime},
 };
 
+pub mod backup;
+pub mod batch;
+pub mod browse;
+pub mod limit;
+pub mod migrate;
 pub mod model;
+pub mod pool;
+#[cfg(any(feature = "toml", feature = "yaml"))]
+#[cfg_attr(feature = "docs", doc(cfg(any(feature = "toml", feature = "yaml"))))]
+pub mod preferences_file;
+pub mod query;
+pub mod retry;
+pub mod session;
 pub use builder::QbitBuilder;
-use reqwest::{header, Client, Method, Response, StatusCode};
+use futures_util::{stream, Stream, TryStreamExt};
+use reqwest::{
+    header,
+    multipart::{Form, Part},
+    Client, Method, RequestBuilder, Response, StatusCode,
+};
 use serde::Serialize;
 use serde_with::skip_serializing_none;
 use tap::{Pipe, TapFallible};
-use tracing::{debug, trace, warn};
+use tracing::{debug, info, trace, warn};
 use url::Url;
 
-use crate::{ext::*, model::*};
+use crate::{
+    ext::*,
+    limit::{Limiter, RateLimit},
+    model::*,
+    query::TorrentQuery,
+    retry::RetryPolicy,
+    session::{SessionKey, SessionStore},
+};
 
 mod builder;
 mod ext;
+#[cfg(test)]
+mod mock;
 
 enum LoginState {
     CookieProvided {
         cookie: String,
     },
+    ApiKeyProvided {
+        header: String,
+        value: String,
+    },
     NotLoggedIn {
         credential: Credential,
     },
     LoggedIn {
         cookie: String,
         credential: Credential,
+        expires: Option<SystemTime>,
     },
 }
 
@@ -42,6 +73,7 @@ impl LoginState {
     fn as_cookie(&self) -> Option<&str> {
         match self {
             Self::CookieProvided { cookie } => Some(cookie),
+            Self::ApiKeyProvided { .. } => None,
             Self::NotLoggedIn { .. } => None,
             Self::LoggedIn { cookie, .. } => Some(cookie),
         }
@@ -49,31 +81,93 @@ impl LoginState {
 
     fn as_credential(&self) -> Option<&Credential> {
         match self {
-            Self::CookieProvided { .. } => None,
+            Self::CookieProvided { .. } | Self::ApiKeyProvided { .. } => None,
             Self::NotLoggedIn { credential } => Some(credential),
             Self::LoggedIn { credential, .. } => Some(credential),
         }
     }
 
-    fn add_cookie(&mut self, cookie: String) {
+    fn is_expired(&self) -> bool {
+        match self {
+            Self::LoggedIn { expires, .. } => expires.is_some_and(|t| t <= SystemTime::now()),
+            _ => false,
+        }
+    }
+
+    fn add_cookie(&mut self, cookie: String, expires: Option<SystemTime>) {
         match self {
-            Self::CookieProvided { .. } => {}
+            Self::CookieProvided { .. } | Self::ApiKeyProvided { .. } => {}
             Self::LoggedIn { credential, .. } | Self::NotLoggedIn { credential } => {
                 *self = Self::LoggedIn {
                     cookie,
                     credential: credential.clone(),
+                    expires,
                 };
             }
         }
     }
 }
 
+/// Authentication attached to each request
+enum Session {
+    Cookie(String),
+    Header { name: String, value: String },
+}
+
+impl Session {
+    fn apply(&self, req: RequestBuilder) -> RequestBuilder {
+        match self {
+            Self::Cookie(cookie) => req.header(header::COOKIE, cookie),
+            Self::Header { name, value } => req.header(name, value),
+        }
+    }
+}
+
 /// Main entry point of the library. It provides a high-level API to interact
 /// with qBittorrent WebUI API.
 pub struct Qbit {
     client: Client,
     endpoint: Url,
-    state: Mutex<LoginState>,
+    state: tokio::sync::Mutex<LoginState>,
+    config: Config,
+    limiter: Limiter,
+    planned: Mutex<Vec<PlannedAction>>,
+}
+
+/// Settings configured with [`QbitBuilder`] that do not change the builder's
+/// type
+#[derive(Clone, Default)]
+struct Config {
+    dry_run: bool,
+    retry: RetryPolicy,
+    rate_limit: RateLimit,
+    timeout: Option<Duration>,
+    headers: header::HeaderMap,
+    basic_auth: Option<(String, Option<String>)>,
+    origin_headers: bool,
+    #[cfg(unix)]
+    unix_socket: Option<PathBuf>,
+    on_cookie_change: Option<CookieHook>,
+    auth_hook: Option<AuthHook>,
+    session_store: Option<Arc<dyn SessionStore>>,
+    #[cfg(feature = "cookies")]
+    cookie_jar: Option<Arc<reqwest::cookie::Jar>>,
+}
+
+/// Callback invoked with the new session cookie after each successful login
+type CookieHook = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Callback applied to every request before it is sent, including login
+type AuthHook = Arc<dyn Fn(RequestBuilder) -> RequestBuilder + Send + Sync>;
+
+/// A `POST` request recorded instead of being sent in dry-run mode. See
+/// [`QbitBuilder::dry_run`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedAction {
+    /// API path, relative to `api/v2/`
+    pub path: &'static str,
+    /// URL-encoded form body
+    pub body: String,
 }
 
 impl Qbit {
@@ -82,6 +176,10 @@ impl Qbit {
         QbitBuilder::new()
     }
 
+    /// # Panics
+    ///
+    /// Panics if the endpoint is invalid. Use [`QbitBuilder::try_build`] to
+    /// handle the error instead.
     pub fn new_with_client<U>(endpoint: U, credential: Credential, client: Client) -> Self
     where
         U: TryInto<Url>,
@@ -94,6 +192,10 @@ impl Qbit {
             .build()
     }
 
+    /// # Panics
+    ///
+    /// Panics if the endpoint is invalid. Use [`QbitBuilder::try_build`] to
+    /// handle the error instead.
     pub fn new<U>(endpoint: U, credential: Credential) -> Self
     where
         U: TryInto<Url>,
@@ -105,7 +207,7 @@ impl Qbit {
     #[deprecated = "Use `QbitBuilder::cookie` instead"]
     pub fn with_cookie(self, cookie: impl Into<String>) -> Self {
         Self {
-            state: Mutex::from(LoginState::CookieProvided {
+            state: tokio::sync::Mutex::new(LoginState::CookieProvided {
                 cookie: cookie.into(),
             }),
             ..self
@@ -113,20 +215,28 @@ impl Qbit {
     }
 
     pub async fn get_cookie(&self) -> Option<String> {
-        self.state
-            .lock()
-            .unwrap()
-            .as_cookie()
-            .map(ToOwned::to_owned)
+        self.state.lock().await.as_cookie().map(ToOwned::to_owned)
+    }
+
+    /// Whether dry-run mode is enabled. See [`QbitBuilder::dry_run`].
+    pub fn is_dry_run(&self) -> bool {
+        self.config.dry_run
+    }
+
+    /// Take all actions recorded in dry-run mode so far, leaving the record
+    /// empty.
+    pub fn take_planned_actions(&self) -> Vec<PlannedAction> {
+        std::mem::take(&mut *self.planned.lock().unwrap_or_else(PoisonError::into_inner))
     }
 
     pub async fn logout(&self) -> Result<()> {
-        self.get("auth/logout").await?.end()
+        self.post("auth/logout", NONE).await?.end()
     }
 
     pub async fn get_version(&self) -> Result<String> {
         self.get("app/version")
             .await?
+            .check()?
             .text()
             .await
             .map_err(Into::into)
@@ -135,17 +245,14 @@ impl Qbit {
     pub async fn get_webapi_version(&self) -> Result<String> {
         self.get("app/webapiVersion")
             .await?
+            .check()?
             .text()
             .await
             .map_err(Into::into)
     }
 
     pub async fn get_build_info(&self) -> Result<BuildInfo> {
-        self.get("app/buildInfo")
-            .await?
-            .json()
-            .await
-            .map_err(Into::into)
+        self.get("app/buildInfo").await?.check()?.parse_json().await
     }
 
     pub async fn shutdown(&self) -> Result<()> {
@@ -155,9 +262,9 @@ impl Qbit {
     pub async fn get_preferences(&self) -> Result<Preferences> {
         self.get("app/preferences")
             .await?
-            .json()
+            .check()?
+            .parse_json()
             .await
-            .map_err(Into::into)
     }
 
     pub async fn set_preferences(
@@ -179,21 +286,185 @@ impl Qbit {
         .end()
     }
 
+    /// Send only the changed fields in `patch`
+    pub async fn set_preferences_patch(&self, patch: &PreferencesPatch) -> Result<()> {
+        #[derive(Serialize)]
+        struct Arg {
+            json: String,
+        }
+
+        self.post(
+            "app/setPreferences",
+            Some(&Arg {
+                json: patch.to_json().to_string(),
+            }),
+        )
+        .await?
+        .end()
+    }
+
+    /// Converge the preferences to `desired`: fetch the current ones and send
+    /// the fields set in `desired` that differ. Returns the changes, which are
+    /// empty if nothing had to be sent.
+    pub async fn apply_preferences(
+        &self,
+        desired: impl Borrow<Preferences> + Send + Sync,
+    ) -> Result<PreferencesPatch> {
+        let patch = self.get_preferences().await?.diff(desired.borrow());
+        if patch.is_empty() {
+            debug!("Preferences already up to date");
+        } else {
+            debug!(changed = patch.len(), "Applying preferences");
+            self.set_preferences_patch(&patch).await?;
+        }
+        Ok(patch)
+    }
+
+    /// Read the schedule of the alternative speed limits from the preferences
+    pub async fn get_speed_schedule(&self) -> Result<SpeedSchedule> {
+        SpeedSchedule::from_preferences(&self.get_preferences().await?).ok_or(Error::BadResponse {
+            explain: "Preferences lack a valid alternative speed limit schedule",
+            context: None,
+        })
+    }
+
+    /// Write the schedule of the alternative speed limits, leaving the other
+    /// preferences untouched
+    pub async fn set_speed_schedule(&self, schedule: &SpeedSchedule) -> Result<()> {
+        self.set_preferences(schedule.to_preferences()).await
+    }
+
     pub async fn get_default_save_path(&self) -> Result<PathBuf> {
         self.get("app/defaultSavePath")
             .await?
+            .check()?
             .text()
             .await
             .map_err(Into::into)
             .map(PathBuf::from)
     }
 
+    /// Absolute paths of the entries of the directory `dir_path` on the
+    /// machine running qBittorrent, limited to directories or files by `mode`
+    pub async fn get_directory_content(
+        &self,
+        dir_path: impl AsRef<Path> + Send + Sync,
+        mode: impl Into<Option<DirectoryContentMode>> + Send + Sync,
+    ) -> Result<Vec<PathBuf>> {
+        #[derive(Serialize)]
+        #[skip_serializing_none]
+        #[serde(rename_all = "camelCase")]
+        struct Arg<'a> {
+            dir_path: &'a Path,
+            mode: Option<DirectoryContentMode>,
+        }
+
+        self.get_with(
+            "app/getDirectoryContent",
+            &Arg {
+                dir_path: dir_path.as_ref(),
+                mode: mode.into(),
+            },
+        )
+        .await?
+        .map_status(|c| match c {
+            StatusCode::BAD_REQUEST => Some(Error::from(ApiError::InvalidDirectoryPath)),
+            StatusCode::NOT_FOUND => Some(Error::from(ApiError::DirectoryNotFound)),
+            _ => None,
+        })?
+        .parse_json()
+        .await
+    }
+
+    pub async fn get_network_interfaces(&self) -> Result<Vec<NetworkInterface>> {
+        self.get("app/networkInterfaceList")
+            .await?
+            .check()?
+            .parse_json()
+            .await
+    }
+
+    /// Addresses of the interface identified by `iface`, i.e. its
+    /// [`NetworkInterface::value`], or of all interfaces if `None`
+    pub async fn get_network_interface_addresses(
+        &self,
+        iface: impl Into<Option<&str>> + Send + Sync,
+    ) -> Result<Vec<String>> {
+        #[derive(Serialize)]
+        struct Arg<'a> {
+            iface: &'a str,
+        }
+
+        self.get_with(
+            "app/networkInterfaceAddressList",
+            &Arg {
+                iface: iface.into().unwrap_or_default(),
+            },
+        )
+        .await?
+        .check()?
+        .parse_json()
+        .await
+    }
+
+    /// Bind qBittorrent to the network interface `iface`, matched by value or
+    /// name, and to `address` or all of its addresses if `None`.
+    ///
+    /// Both are checked to exist first, returning
+    /// [`Error::InvalidPreferences`] otherwise, so that qBittorrent is never
+    /// bound to an interface it cannot use.
+    pub async fn bind_network_interface(
+        &self,
+        iface: impl AsRef<str> + Send + Sync,
+        address: impl Into<Option<&str>> + Send + Sync,
+    ) -> Result<()> {
+        let iface = iface.as_ref();
+        let address = address.into();
+        let invalid =
+            |field, message| Error::InvalidPreferences(vec![InvalidPreference { field, message }]);
+
+        let interface = self
+            .get_network_interfaces()
+            .await?
+            .into_iter()
+            .find(|i| i.value == iface || i.name == iface)
+            .ok_or_else(|| {
+                invalid(
+                    "current_network_interface",
+                    format!("no network interface {iface:?}"),
+                )
+            })?;
+        if let Some(address) = address {
+            let addresses = self
+                .get_network_interface_addresses(interface.value.as_str())
+                .await?;
+            if !addresses.iter().any(|a| a == address) {
+                return Err(invalid(
+                    "current_interface_address",
+                    format!("{address} is not an address of {}", interface.name),
+                ));
+            }
+        }
+
+        debug!(
+            interface = interface.value,
+            address, "Binding to network interface"
+        );
+        self.set_preferences(Preferences {
+            current_network_interface: Some(interface.value),
+            current_interface_name: Some(interface.name),
+            current_interface_address: Some(address.unwrap_or_default().to_owned()),
+            ..Default::default()
+        })
+        .await
+    }
+
     pub async fn get_logs(&self, arg: impl Borrow<GetLogsArg> + Send + Sync) -> Result<Vec<Log>> {
         self.get_with("log/main", arg.borrow())
             .await?
-            .json()
+            .check()?
+            .parse_json()
             .await
-            .map_err(Into::into)
     }
 
     pub async fn get_peer_logs(
@@ -213,9 +484,9 @@ impl Qbit {
             },
         )
         .await?
-        .json()
+        .check()?
+        .parse_json()
         .await
-        .map_err(Into::into)
     }
 
     pub async fn sync(&self, rid: impl Into<Option<i64>> + Send + Sync) -> Result<SyncData> {
@@ -227,9 +498,9 @@ impl Qbit {
 
         self.get_with("sync/maindata", &Arg { rid: rid.into() })
             .await?
-            .json()
+            .check()?
+            .parse_json()
             .await
-            .map_err(Into::into)
     }
 
     pub async fn get_torrent_peers(
@@ -252,49 +523,51 @@ impl Qbit {
         )
         .await
         .and_then(|r| r.map_status(TORRENT_NOT_FOUND))?
-        .json()
+        .parse_json()
         .await
-        .map_err(Into::into)
     }
 
     pub async fn get_transfer_info(&self) -> Result<TransferInfo> {
-        self.get("transfer/info")
-            .await?
-            .json()
-            .await
-            .map_err(Into::into)
+        self.get("transfer/info").await?.check()?.parse_json().await
     }
 
     pub async fn get_speed_limits_mode(&self) -> Result<bool> {
-        self.get("transfer/speedLimitsMode")
-            .await?
-            .text()
-            .await
-            .map_err(Into::into)
-            .and_then(|s| match s.as_str() {
-                "0" => Ok(false),
-                "1" => Ok(true),
-                _ => Err(Error::BadResponse {
-                    explain: "Received non-number response body on `transfer/speedLimitsMode`",
-                }),
-            })
+        self.get_parsed(
+            "transfer/speedLimitsMode",
+            "Received non-number response body on `transfer/speedLimitsMode`",
+            |s| match s {
+                "0" => Some(false),
+                "1" => Some(true),
+                _ => None,
+            },
+        )
+        .await
     }
 
     pub async fn toggle_speed_limits_mode(&self) -> Result<()> {
-        self.get("transfer/toggleSpeedLimitsMode").await?.end()
+        self.post("transfer/toggleSpeedLimitsMode", NONE)
+            .await?
+            .end()
+    }
+
+    /// Enable or disable the alternative speed limits. Only toggles the mode
+    /// if it differs from `enabled`, so calling this repeatedly is harmless.
+    /// The mode is read first, a concurrent toggle in between is not detected.
+    pub async fn set_speed_limits_mode(&self, enabled: bool) -> Result<()> {
+        if self.get_speed_limits_mode().await? == enabled {
+            debug!(enabled, "Speed limits mode already set");
+            return Ok(());
+        }
+        self.toggle_speed_limits_mode().await
     }
 
     pub async fn get_download_limit(&self) -> Result<u64> {
-        self.get("transfer/downloadLimit")
-            .await?
-            .text()
-            .await
-            .map_err(Into::into)
-            .and_then(|s| {
-                s.parse().map_err(|_| Error::BadResponse {
-                    explain: "Received non-number response body on `transfer/downloadLimit`",
-                })
-            })
+        self.get_parsed(
+            "transfer/downloadLimit",
+            "Received non-number response body on `transfer/downloadLimit`",
+            |s| s.parse().ok(),
+        )
+        .await
     }
 
     pub async fn set_download_limit(&self, limit: u64) -> Result<()> {
@@ -309,16 +582,12 @@ impl Qbit {
     }
 
     pub async fn get_upload_limit(&self) -> Result<u64> {
-        self.get("transfer/uploadLimit")
-            .await?
-            .text()
-            .await
-            .map_err(Into::into)
-            .and_then(|s| {
-                s.parse().map_err(|_| Error::BadResponse {
-                    explain: "Received non-number response body on `transfer/uploadLimit`",
-                })
-            })
+        self.get_parsed(
+            "transfer/uploadLimit",
+            "Received non-number response body on `transfer/uploadLimit`",
+            |s| s.parse().ok(),
+        )
+        .await
     }
 
     pub async fn set_upload_limit(&self, limit: u64) -> Result<()> {
@@ -351,9 +620,71 @@ impl Qbit {
     pub async fn get_torrent_list(&self, arg: GetTorrentListArg) -> Result<Vec<Torrent>> {
         self.get_with("torrents/info", &arg)
             .await?
-            .json()
+            .check()?
+            .parse_json()
             .await
-            .map_err(Into::into)
+    }
+
+    /// Fetch torrents matching `query`. Predicates supported by
+    /// `torrents/info` are sent to the server, the rest are evaluated locally.
+    pub async fn query_torrents(&self, query: &TorrentQuery) -> Result<Vec<Torrent>> {
+        let (arg, residual) = query.split();
+        let mut torrents = self.get_torrent_list(arg).await?;
+        torrents.retain(|t| residual.matches(t));
+        Ok(torrents)
+    }
+
+    /// Walk the torrent list page by page using `limit` and `offset`, yielding
+    /// torrents one at a time instead of buffering the whole list.
+    ///
+    /// `limit` and `offset` in `arg` bound the overall range to walk; a
+    /// negative `offset` is treated as `0`. If no sort key is given, torrents
+    /// are sorted by hash so that pages do not overlap or skip entries.
+    /// qBittorrent only sorts by a single key, so with another key, torrents
+    /// sharing a value may be ordered differently from one page to the next,
+    /// and be returned twice or not at all. Torrents added or removed while
+    /// walking may also shift pages.
+    pub fn torrents_paged(
+        &self,
+        arg: GetTorrentListArg,
+        page_size: u64,
+    ) -> impl Stream<Item = Result<Torrent>> + Send + '_ {
+        let page_size = page_size.max(1);
+        let start = arg.offset.unwrap_or(0).max(0);
+        let arg = GetTorrentListArg {
+            sort: arg.sort.or(Some(TorrentSortKey::Hash)),
+            ..arg
+        };
+
+        stream::try_unfold(Some((start, arg.limit)), move |state| {
+            let arg = arg.clone();
+            async move {
+                let Some((offset, remaining)) = state else {
+                    return Ok::<_, Error>(None);
+                };
+                let limit = remaining.map_or(page_size, |r| r.min(page_size));
+                if limit == 0 {
+                    return Ok(None);
+                }
+
+                trace!(offset, limit, "Fetching torrent page");
+                let page = self
+                    .get_torrent_list(GetTorrentListArg {
+                        limit: Some(limit),
+                        offset: Some(offset),
+                        ..arg
+                    })
+                    .await?;
+
+                let len = page.len() as u64;
+                let next =
+                    (len == limit).then(|| (offset + len as i64, remaining.map(|r| r - len)));
+
+                Ok(Some((page, next)))
+            }
+        })
+        .map_ok(|page| stream::iter(page.into_iter().map(Ok)))
+        .try_flatten()
     }
 
     pub async fn get_torrent_properties(
@@ -363,9 +694,8 @@ impl Qbit {
         self.get_with("torrents/properties", &HashArg::new(hash.as_ref()))
             .await
             .and_then(|r| r.map_status(TORRENT_NOT_FOUND))?
-            .json()
+            .parse_json()
             .await
-            .map_err(Into::into)
     }
 
     pub async fn get_torrent_trackers(
@@ -375,9 +705,8 @@ impl Qbit {
         self.get_with("torrents/trackers", &HashArg::new(hash.as_ref()))
             .await
             .and_then(|r| r.map_status(TORRENT_NOT_FOUND))?
-            .json()
+            .parse_json()
             .await
-            .map_err(Into::into)
     }
 
     pub async fn get_torrent_web_seeds(
@@ -387,9 +716,8 @@ impl Qbit {
         self.get_with("torrents/webseeds", &HashArg::new(hash.as_ref()))
             .await
             .and_then(|r| r.map_status(TORRENT_NOT_FOUND))?
-            .json()
+            .parse_json()
             .await
-            .map_err(Into::into)
     }
 
     pub async fn get_torrent_contents(
@@ -413,9 +741,8 @@ impl Qbit {
         )
         .await
         .and_then(|r| r.map_status(TORRENT_NOT_FOUND))?
-        .json()
+        .parse_json()
         .await
-        .map_err(Into::into)
     }
 
     pub async fn get_torrent_pieces_states(
@@ -425,9 +752,8 @@ impl Qbit {
         self.get_with("torrents/pieceStates", &HashArg::new(hash.as_ref()))
             .await
             .and_then(|r| r.map_status(TORRENT_NOT_FOUND))?
-            .json()
+            .parse_json()
             .await
-            .map_err(Into::into)
     }
 
     pub async fn get_torrent_pieces_hashes(
@@ -437,9 +763,8 @@ impl Qbit {
         self.get_with("torrents/pieceHashes", &HashArg::new(hash.as_ref()))
             .await
             .and_then(|r| r.map_status(TORRENT_NOT_FOUND))?
-            .json()
+            .parse_json()
             .await
-            .map_err(Into::into)
     }
 
     pub async fn pause_torrents(&self, hashes: impl Into<Hashes> + Send + Sync) -> Result<()> {
@@ -490,7 +815,37 @@ impl Qbit {
     }
 
     pub async fn add_torrent(&self, arg: impl Borrow<AddTorrentArg> + Send + Sync) -> Result<()> {
-        self.post("torrents/add", Some(arg.borrow())).await?.end()
+        let arg = arg.borrow();
+        let res = match &arg.source {
+            TorrentSource::Urls { .. } => self.post("torrents/add", Some(arg)).await?,
+            TorrentSource::TorrentFiles { torrents } => {
+                self.post_multipart("torrents/add", arg, ("torrents", "file.torrent", torrents))
+                    .await?
+            }
+        };
+        res.map_status(|c| {
+            if c == StatusCode::UNSUPPORTED_MEDIA_TYPE {
+                Some(Error::from(ApiError::InvalidTorrent))
+            } else {
+                None
+            }
+        })?
+        .end()
+    }
+
+    /// Download the .torrent file of a torrent
+    pub async fn export_torrent(&self, hash: impl AsRef<str> + Send + Sync) -> Result<Vec<u8>> {
+        self.get_with("torrents/export", &HashArg::new(hash.as_ref()))
+            .await?
+            .map_status(|c| match c {
+                StatusCode::NOT_FOUND => Some(Error::from(ApiError::TorrentNotFound)),
+                StatusCode::CONFLICT => Some(Error::from(ApiError::MetadataNotDownloaded)),
+                _ => None,
+            })?
+            .bytes()
+            .await
+            .map(|b| b.to_vec())
+            .map_err(Into::into)
     }
 
     pub async fn add_trackers(
@@ -513,9 +868,7 @@ impl Qbit {
         )
         .await
         .and_then(|r| r.map_status(TORRENT_NOT_FOUND))?
-        .json()
-        .await
-        .map_err(Into::into)
+        .end()
     }
 
     pub async fn edit_trackers(
@@ -540,14 +893,12 @@ impl Qbit {
         )
         .await?
         .map_status(|c| match c {
-            StatusCode::BAD_REQUEST => Some(Error::ApiError(ApiError::InvalidTrackerUrl)),
-            StatusCode::NOT_FOUND => Some(Error::ApiError(ApiError::TorrentNotFound)),
-            StatusCode::CONFLICT => Some(Error::ApiError(ApiError::ConflictTrackerUrl)),
+            StatusCode::BAD_REQUEST => Some(Error::from(ApiError::InvalidTrackerUrl)),
+            StatusCode::NOT_FOUND => Some(Error::from(ApiError::TorrentNotFound)),
+            StatusCode::CONFLICT => Some(Error::from(ApiError::ConflictTrackerUrl)),
             _ => None,
         })?
-        .json()
-        .await
-        .map_err(Into::into)
+        .end()
     }
 
     pub async fn remove_trackers(
@@ -568,8 +919,12 @@ impl Qbit {
                 urls: urls.into(),
             }),
         )
-        .await
-        .and_then(|r| r.map_status(TORRENT_NOT_FOUND))?
+        .await?
+        .map_status(|c| match c {
+            StatusCode::NOT_FOUND => Some(Error::from(ApiError::TorrentNotFound)),
+            StatusCode::CONFLICT => Some(Error::from(ApiError::TrackerUrlNotFound)),
+            _ => None,
+        })?
         .end()
     }
 
@@ -595,7 +950,7 @@ impl Qbit {
         .and_then(|r| {
             r.map_status(|c| {
                 if c == StatusCode::BAD_REQUEST {
-                    Some(Error::ApiError(ApiError::InvalidPeers))
+                    Some(Error::from(ApiError::InvalidPeers))
                 } else {
                     None
                 }
@@ -609,7 +964,7 @@ impl Qbit {
             .await?
             .map_status(|c| {
                 if c == StatusCode::CONFLICT {
-                    Some(Error::ApiError(ApiError::QueueingDisabled))
+                    Some(Error::from(ApiError::QueueingDisabled))
                 } else {
                     None
                 }
@@ -622,7 +977,7 @@ impl Qbit {
             .await?
             .map_status(|c| {
                 if c == StatusCode::CONFLICT {
-                    Some(Error::ApiError(ApiError::QueueingDisabled))
+                    Some(Error::from(ApiError::QueueingDisabled))
                 } else {
                     None
                 }
@@ -635,7 +990,7 @@ impl Qbit {
             .await?
             .map_status(|c| {
                 if c == StatusCode::CONFLICT {
-                    Some(Error::ApiError(ApiError::QueueingDisabled))
+                    Some(Error::from(ApiError::QueueingDisabled))
                 } else {
                     None
                 }
@@ -648,7 +1003,7 @@ impl Qbit {
             .await?
             .map_status(|c| {
                 if c == StatusCode::CONFLICT {
-                    Some(Error::ApiError(ApiError::QueueingDisabled))
+                    Some(Error::from(ApiError::QueueingDisabled))
                 } else {
                     None
                 }
@@ -679,9 +1034,9 @@ impl Qbit {
         )
         .await?
         .map_status(|c| match c {
-            StatusCode::BAD_REQUEST => panic!("Invalid priority or id. This is a bug."),
-            StatusCode::NOT_FOUND => Some(Error::ApiError(ApiError::TorrentNotFound)),
-            StatusCode::CONFLICT => Some(Error::ApiError(ApiError::MetaNotDownloadedOrIdNotFound)),
+            StatusCode::BAD_REQUEST => Some(Error::from(ApiError::InvalidFilePriority)),
+            StatusCode::NOT_FOUND => Some(Error::from(ApiError::TorrentNotFound)),
+            StatusCode::CONFLICT => Some(Error::from(ApiError::MetaNotDownloadedOrIdNotFound)),
             _ => None,
         })?;
         Ok(())
@@ -693,9 +1048,9 @@ impl Qbit {
     ) -> Result<HashMap<String, u64>> {
         self.get_with("torrents/downloadLimit", &HashesArg::new(hashes))
             .await?
-            .json()
+            .check()?
+            .parse_json()
             .await
-            .map_err(Into::into)
     }
 
     pub async fn set_torrent_download_limit(
@@ -735,9 +1090,9 @@ impl Qbit {
     ) -> Result<HashMap<String, u64>> {
         self.get_with("torrents/uploadLimit", &HashesArg::new(hashes))
             .await?
-            .json()
+            .check()?
+            .parse_json()
             .await
-            .map_err(Into::into)
     }
 
     pub async fn set_torrent_upload_limit(
@@ -782,9 +1137,9 @@ impl Qbit {
         )
         .await?
         .map_status(|c| match c {
-            StatusCode::BAD_REQUEST => Some(Error::ApiError(ApiError::SavePathEmpty)),
-            StatusCode::FORBIDDEN => Some(Error::ApiError(ApiError::NoWriteAccess)),
-            StatusCode::CONFLICT => Some(Error::ApiError(ApiError::UnableToCreateDir)),
+            StatusCode::BAD_REQUEST => Some(Error::from(ApiError::SavePathEmpty)),
+            StatusCode::FORBIDDEN => Some(Error::from(ApiError::NoWriteAccess)),
+            StatusCode::CONFLICT => Some(Error::from(ApiError::UnableToCreateDir)),
             _ => None,
         })?
         .end()
@@ -810,8 +1165,8 @@ impl Qbit {
         )
         .await?
         .map_status(|c| match c {
-            StatusCode::NOT_FOUND => Some(Error::ApiError(ApiError::TorrentNotFound)),
-            StatusCode::CONFLICT => panic!("Name should not be empty. This is a bug."),
+            StatusCode::NOT_FOUND => Some(Error::from(ApiError::TorrentNotFound)),
+            StatusCode::CONFLICT => Some(Error::from(ApiError::TorrentNameEmpty)),
             _ => None,
         })?
         .end()
@@ -838,7 +1193,7 @@ impl Qbit {
         .await?
         .map_status(|c| {
             if c == StatusCode::CONFLICT {
-                Some(Error::ApiError(ApiError::CategoryNotFound))
+                Some(Error::from(ApiError::CategoryNotFound))
             } else {
                 None
             }
@@ -849,9 +1204,9 @@ impl Qbit {
     pub async fn get_categories(&self) -> Result<HashMap<String, Category>> {
         self.get("torrents/categories")
             .await?
-            .json()
+            .check()?
+            .parse_json()
             .await
-            .map_err(Into::into)
     }
 
     pub async fn add_category<T: AsRef<str> + Send + Sync>(
@@ -874,6 +1229,11 @@ impl Qbit {
             }),
         )
         .await?
+        .map_status(|c| match c {
+            StatusCode::BAD_REQUEST => Some(Error::from(ApiError::CategoryNameEmpty)),
+            StatusCode::CONFLICT => Some(Error::from(ApiError::CategoryNameInvalid)),
+            _ => None,
+        })?
         .end()
     }
 
@@ -890,19 +1250,17 @@ impl Qbit {
         }
 
         self.post(
-            "torrents/createCategory",
+            "torrents/editCategory",
             Some(&Arg {
                 category: category.as_str(),
                 save_path: save_path.as_ref(),
             }),
         )
         .await?
-        .map_status(|c| {
-            if c == StatusCode::CONFLICT {
-                Some(Error::ApiError(ApiError::CategoryEditingFailed))
-            } else {
-                None
-            }
+        .map_status(|c| match c {
+            StatusCode::BAD_REQUEST => Some(Error::from(ApiError::CategoryNameEmpty)),
+            StatusCode::CONFLICT => Some(Error::from(ApiError::CategoryEditingFailed)),
+            _ => None,
         })?
         .end()
     }
@@ -972,11 +1330,7 @@ impl Qbit {
     }
 
     pub async fn get_all_tags(&self) -> Result<Vec<String>> {
-        self.get("torrents/tags")
-            .await?
-            .json()
-            .await
-            .map_err(Into::into)
+        self.get("torrents/tags").await?.check()?.parse_json().await
     }
 
     pub async fn create_tags(&self, tags: impl Into<Sep<String, ','>> + Send + Sync) -> Result<()> {
@@ -1124,12 +1478,11 @@ impl Qbit {
             }),
         )
         .await?
-        .map_status(|c| {
-            if c == StatusCode::CONFLICT {
-                Error::ApiError(ApiError::InvalidPath).pipe(Some)
-            } else {
-                None
+        .map_status(|c| match c {
+            StatusCode::BAD_REQUEST | StatusCode::CONFLICT => {
+                Error::from(ApiError::InvalidPath).pipe(Some)
             }
+            _ => None,
         })?
         .end()
     }
@@ -1157,58 +1510,192 @@ impl Qbit {
             }),
         )
         .await?
-        .map_status(|c| {
-            if c == StatusCode::CONFLICT {
-                Error::ApiError(ApiError::InvalidPath).pipe(Some)
-            } else {
-                None
+        .map_status(|c| match c {
+            StatusCode::BAD_REQUEST | StatusCode::CONFLICT => {
+                Error::from(ApiError::InvalidPath).pipe(Some)
             }
+            _ => None,
         })?
         .end()
     }
 
-    fn url(&self, path: &'static str) -> Url {
+    fn url(&self, path: &'static str) -> Result<Url> {
         self.endpoint
             .join("api/v2/")
-            .unwrap()
-            .join(path)
-            .expect("Invalid API endpoint")
-    }
-
-    fn state(&self) -> MutexGuard<'_, LoginState> {
-        self.state.lock().unwrap()
+            .and_then(|base| base.join(path))
+            .map_err(|e| Error::InvalidEndpoint(e.to_string()))
     }
 
     /// Log in to qBittorrent. Set force to `true` to forcefully re-login
     /// regardless if cookie is already set.
+    ///
+    /// Concurrent logins are coalesced: only one `auth/login` request is in
+    /// flight at a time, and callers waiting on it reuse its cookie.
     pub async fn login(&self, force: bool) -> Result<()> {
-        let re_login = force || { self.state().as_cookie().is_none() };
-        if re_login {
-            debug!("Cookie not found, logging in");
-            self.client
-                .request(Method::POST, self.url("auth/login"))
-                .pipe(|req| {
-                    req.form(
-                        self.state()
-                            .as_credential()
-                            .expect("Credential should be set if cookie is not set"),
-                    )
-                })
-                .send()
-                .await?
-                .map_status(|code| match code as _ {
-                    StatusCode::FORBIDDEN => Some(Error::ApiError(ApiError::IpBanned)),
-                    _ => None,
-                })?
-                .extract::<Cookie>()?
-                .pipe(|Cookie(cookie)| self.state.lock().unwrap().add_cookie(cookie));
-
-            debug!("Log in success");
+        let stale = if force {
+            let state = self.state.lock().await;
+            if let Some(credential) = state.as_credential() {
+                self.forget_session(credential);
+            }
+            state.as_cookie().map(ToOwned::to_owned)
         } else {
-            trace!("Already logged in, skipping");
+            None
+        };
+        self.ensure_login(stale.as_deref()).await.map(drop)
+    }
+
+    /// Return the current session, logging in first if there is no cookie or
+    /// it is the same as `stale`, i.e. it has been rejected by the server and
+    /// no one else has refreshed it in the meantime.
+    async fn ensure_login(&self, stale: Option<&str>) -> Result<Session> {
+        let mut state = self.state.lock().await;
+        let expired = state.is_expired();
+
+        if let LoginState::ApiKeyProvided { header, value } = &*state {
+            return Ok(Session::Header {
+                name: header.clone(),
+                value: value.clone(),
+            });
         }
 
-        Ok(())
+        match state.as_cookie() {
+            Some(cookie) if !expired && Some(cookie) != stale => {
+                trace!("Already logged in, skipping");
+                return Ok(Session::Cookie(cookie.to_owned()));
+            }
+            Some(_) if expired => debug!("Cookie expired, logging in again"),
+            Some(_) => debug!("Cookie rejected, logging in again"),
+            None => debug!("Cookie not found, logging in"),
+        }
+
+        let credential = state
+            .as_credential()
+            .ok_or(Error::from(ApiError::NotLoggedIn))?;
+
+        if stale.is_some() || expired {
+            self.forget_session(credential);
+        } else if let Some(cookie) = self.restore_session(credential) {
+            debug!("Restored cookie from session store");
+            state.add_cookie(cookie.clone(), None);
+            return Ok(Session::Cookie(cookie));
+        }
+
+        let cookies = self
+            .send_login(credential)
+            .await?
+            .map_status(|code| match code as _ {
+                StatusCode::FORBIDDEN => Some(Error::from(ApiError::IpBanned)),
+                _ => None,
+            })?
+            .extract::<SessionCookies>()?;
+        let cookie = cookies.header();
+        trace!(count = cookies.cookies.len(), "Received cookies");
+
+        #[cfg(feature = "cookies")]
+        if let Some(jar) = &self.config.cookie_jar {
+            for c in &cookies.cookies {
+                jar.add_cookie_str(&c.raw, &self.endpoint);
+            }
+        }
+
+        self.save_session(credential, &cookie);
+        state.add_cookie(cookie.clone(), cookies.expires());
+        drop(state);
+        debug!("Log in success");
+
+        if let Some(hook) = &self.config.on_cookie_change {
+            hook(&cookie);
+        }
+
+        Ok(Session::Cookie(cookie))
+    }
+
+    /// Send `auth/login` with the configured timeout and rate limit, retrying
+    /// connection failures as the retry policy allows. Callers hold the login
+    /// lock, so the request must not hang indefinitely.
+    async fn send_login(&self, credential: &Credential) -> Result<Response> {
+        const PATH: &str = "auth/login";
+        let policy = &self.config.retry;
+        let mut attempt = 1;
+
+        loop {
+            let mut req = self
+                .client
+                .request(Method::POST, self.url(PATH)?)
+                .form(credential);
+            if let Some(timeout) = self.config.timeout {
+                req = req.timeout(timeout);
+            }
+            let req = self.prepare(req);
+
+            let permit = self.limiter.acquire(PATH).await;
+            let res = req.send().await;
+            drop(permit);
+
+            match res {
+                Ok(res) => return with_context(res, Method::POST, PATH).await,
+                Err(e)
+                    if attempt < policy.max_attempts && policy.retry_connect && e.is_connect() =>
+                {
+                    let backoff = policy.backoff(attempt);
+                    warn!(error = %e, ?backoff, attempt, "Login failed, retrying");
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    let context = ErrorContext::new(Method::POST, PATH, e.status());
+                    return Err(Error::from(e).with_context(context));
+                }
+            }
+        }
+    }
+
+    /// Add the configured default headers, basic auth and `Referer`/`Origin`
+    /// to `req`, then run the auth hook
+    fn prepare(&self, mut req: RequestBuilder) -> RequestBuilder {
+        if !self.config.headers.is_empty() {
+            req = req.headers(self.config.headers.clone());
+        }
+        if let Some((username, password)) = &self.config.basic_auth {
+            req = req.basic_auth(username, password.as_ref());
+        }
+        if self.config.origin_headers {
+            req = req
+                .header(header::ORIGIN, self.endpoint.origin().ascii_serialization())
+                .header(header::REFERER, self.endpoint.as_str());
+        }
+        match &self.config.auth_hook {
+            Some(hook) => hook(req),
+            None => req,
+        }
+    }
+
+    fn restore_session(&self, credential: &Credential) -> Option<String> {
+        let store = self.config.session_store.as_ref()?;
+        let key = SessionKey::new(&self.endpoint, credential.username());
+        store
+            .load(&key)
+            .tap_err(|error| warn!(%key, %error, "Failed to load session"))
+            .ok()
+            .flatten()
+    }
+
+    fn save_session(&self, credential: &Credential, cookie: &str) {
+        if let Some(store) = &self.config.session_store {
+            let key = SessionKey::new(&self.endpoint, credential.username());
+            if let Err(error) = store.save(&key, cookie) {
+                warn!(%key, %error, "Failed to save session");
+            }
+        }
+    }
+
+    fn forget_session(&self, credential: &Credential) {
+        if let Some(store) = &self.config.session_store {
+            let key = SessionKey::new(&self.endpoint, credential.username());
+            if let Err(error) = store.remove(&key) {
+                warn!(%key, %error, "Failed to remove session");
+            }
+        }
     }
 
     async fn request(
@@ -1217,53 +1704,114 @@ impl Qbit {
         path: &'static str,
         body: Option<&(impl Serialize + Sync)>,
     ) -> Result<Response> {
-        for i in 0..3 {
-            // If it's not the first attempt, we need to re-login
-            self.login(i != 0).await?;
-
-            let mut req =
-                self.client
-                    .request(method.clone(), self.url(path))
-                    .header(header::COOKIE, {
-                        self.state()
-                            .as_cookie()
-                            .expect("Cookie should be set after login")
-                    });
-
-            if let Some(ref body) = body {
-                match method {
-                    Method::GET => req = req.query(body),
-                    Method::POST => req = req.form(body),
-                    _ => unreachable!("Only GET and POST are supported"),
-                }
+        let kind = method.clone();
+        self.send(method, path, |req| match body {
+            Some(body) => match kind {
+                Method::GET => req.query(body),
+                Method::POST => req.form(body),
+                _ => unreachable!("Only GET and POST are supported"),
+            },
+            None => req,
+        })
+        .await
+    }
+
+    /// Send a request, logging in and retrying as needed. `attach` adds the
+    /// body to each attempt.
+    ///
+    /// A `403` response to a cookie session logs in again once, without
+    /// counting as an attempt. Any later `403` is returned, so that endpoints
+    /// can map it to their own error.
+    async fn send(
+        &self,
+        method: Method,
+        path: &'static str,
+        attach: impl Fn(RequestBuilder) -> RequestBuilder + Sync,
+    ) -> Result<Response> {
+        let policy = &self.config.retry;
+        let mut stale = None;
+        let mut relogged = false;
+        let mut attempt = 1;
+
+        loop {
+            let last = attempt >= policy.max_attempts;
+            let session = self.ensure_login(stale.take().as_deref()).await?;
+
+            let mut req = self
+                .client
+                .request(method.clone(), self.url(path)?)
+                .pipe(|req| session.apply(req));
+
+            if let Some(timeout) = self.config.timeout {
+                req = req.timeout(timeout);
             }
+            let req = self.prepare(attach(req));
             trace!(request = ?req, "Sending request");
-            let res = req
-                .send()
-                .await?
-                .map_status(|code| match code as _ {
-                    StatusCode::FORBIDDEN => Some(Error::ApiError(ApiError::NotLoggedIn)),
-                    _ => None,
-                })
-                .tap_ok(|response| trace!(?response));
 
-            match res {
-                Err(Error::ApiError(ApiError::NotLoggedIn)) => {
-                    // Retry
-                    warn!("Cookie is not valid, retrying");
+            // Only covers the request and the response headers, see
+            // `RateLimit::max_in_flight`
+            let permit = self.limiter.acquire(path).await;
+            let res = req.send().await;
+            drop(permit);
+
+            let res = match res {
+                Ok(res) => res,
+                Err(e) if !last && policy.should_retry_error(&e, path) => {
+                    let backoff = policy.backoff(attempt);
+                    warn!(error = %e, ?backoff, attempt, "Request failed, retrying");
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                    continue;
+                }
+                Err(e) => {
+                    let context = ErrorContext::new(method, path, e.status());
+                    return Err(Error::from(e).with_context(context));
+                }
+            };
+            let status = res.status();
+
+            if status == StatusCode::FORBIDDEN && !relogged {
+                if let Session::Cookie(cookie) = session {
+                    warn!("Cookie is not valid, logging in again");
+                    stale = Some(cookie);
+                    relogged = true;
+                    continue;
                 }
-                Err(e) => return Err(e),
-                Ok(t) => return Ok(t),
             }
-        }
+            if !last && policy.should_retry_status(status, path) {
+                let backoff = policy.backoff(attempt);
+                warn!(%status, ?backoff, attempt, "Request failed, retrying");
+                tokio::time::sleep(backoff).await;
+                attempt += 1;
+                continue;
+            }
 
-        Err(Error::ApiError(ApiError::NotLoggedIn))
+            trace!(response = ?res);
+            return with_context(res, method, path).await;
+        }
     }
 
     async fn get(&self, path: &'static str) -> Result<Response> {
         self.request(Method::GET, path, NONE).await
     }
 
+    /// `GET` `path` and parse the response body with `parse`, reporting the
+    /// body in the error if it fails
+    async fn get_parsed<T>(
+        &self,
+        path: &'static str,
+        explain: &'static str,
+        parse: impl FnOnce(&str) -> Option<T> + Send,
+    ) -> Result<T> {
+        let res = self.get(path).await?.check()?;
+        let context = res.error_context();
+        let text = res.text().await?;
+        parse(&text).ok_or_else(|| Error::BadResponse {
+            explain,
+            context: context.map(|c| Box::new(c.with_body(&text))),
+        })
+    }
+
     async fn get_with(
         &self,
         path: &'static str,
@@ -1277,31 +1825,287 @@ impl Qbit {
         path: &'static str,
         body: Option<&(impl Serialize + Sync)>,
     ) -> Result<Response> {
+        if self.config.dry_run {
+            return self.plan(path, body);
+        }
         self.request(Method::POST, path, body).await
     }
+
+    /// `POST` a `multipart/form-data` body with the scalar fields of `fields`
+    /// and `file` as `(name, filename, data)`. Other values of `fields`, e.g.
+    /// file contents, are skipped.
+    async fn post_multipart(
+        &self,
+        path: &'static str,
+        fields: &(impl Serialize + Sync),
+        (name, filename, data): (&'static str, &'static str, &[u8]),
+    ) -> Result<Response> {
+        let fields = match serde_json::to_value(fields)? {
+            serde_json::Value::Object(map) => map
+                .into_iter()
+                .filter_map(|(k, v)| match v {
+                    serde_json::Value::String(s) => Some((k, s)),
+                    serde_json::Value::Number(n) => Some((k, n.to_string())),
+                    serde_json::Value::Bool(b) => Some((k, b.to_string())),
+                    _ => None,
+                })
+                .collect::<Vec<_>>(),
+            _ => vec![],
+        };
+
+        if self.config.dry_run {
+            let mut body = url::form_urlencoded::Serializer::new(String::new());
+            body.extend_pairs(&fields)
+                .append_pair(name, &format!("<{} bytes>", data.len()));
+            return self.record(path, body.finish());
+        }
+        // A form is consumed when sent, so build a new one for each attempt
+        self.send(Method::POST, path, |req| {
+            let part = Part::bytes(data.to_vec())
+                .file_name(filename)
+                .mime_str("application/x-bittorrent")
+                .expect("valid mime type");
+            let form = fields
+                .iter()
+                .fold(Form::new(), |form, (k, v)| form.text(k.clone(), v.clone()))
+                .part(name, part);
+            req.multipart(form)
+        })
+        .await
+    }
+
+    /// Record a `POST` request in dry-run mode and return an empty successful
+    /// response in its place.
+    fn plan(&self, path: &'static str, body: Option<&(impl Serialize + Sync)>) -> Result<Response> {
+        let mut req = self.client.request(Method::POST, self.url(path)?);
+        if let Some(body) = body {
+            req = req.form(body);
+        }
+        let body = req
+            .build()?
+            .body()
+            .and_then(|b| b.as_bytes())
+            .map(|b| String::from_utf8_lossy(b).into_owned())
+            .unwrap_or_default();
+
+        self.record(path, body)
+    }
+
+    fn record(&self, path: &'static str, body: String) -> Result<Response> {
+        info!(path, body, "Dry run, request not sent");
+        self.planned
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(PlannedAction { path, body });
+
+        Ok(http::Response::new(Vec::<u8>::new()).into())
+    }
 }
 
 const NONE: Option<&'static ()> = Option::None;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
-    #[error("Http error: {0}")]
-    HttpError(#[from] reqwest::Error),
+    #[error("Http error: {error}{}", fmt_context(context))]
+    HttpError {
+        #[source]
+        error: reqwest::Error,
+        context: Option<Box<ErrorContext>>,
+    },
 
-    #[error("API Returned bad response: {explain}")]
-    BadResponse { explain: &'static str },
+    #[error("API Returned bad response: {explain}{}", fmt_context(context))]
+    BadResponse {
+        explain: &'static str,
+        context: Option<Box<ErrorContext>>,
+    },
 
-    #[error("API returned unknown status code: {0}")]
-    UnknownHttpCode(StatusCode),
+    #[error("API returned unknown status code: {status}{}", fmt_context(context))]
+    UnknownHttpCode {
+        status: StatusCode,
+        context: Option<Box<ErrorContext>>,
+    },
 
     #[error("Non ASCII header")]
     NonAsciiHeader,
 
-    #[error(transparent)]
-    ApiError(#[from] ApiError),
+    #[error("Invalid endpoint: {0}")]
+    InvalidEndpoint(String),
+
+    #[error("Basic auth and the API key both use the `Authorization` header")]
+    ConflictingAuth,
+
+    #[error("{error}{}", fmt_context(context))]
+    ApiError {
+        error: ApiError,
+        context: Option<Box<ErrorContext>>,
+    },
+
+    #[error("serde_json error: {error}{}", fmt_context(context))]
+    SerdeJsonError {
+        #[source]
+        error: serde_json::Error,
+        context: Option<Box<ErrorContext>>,
+    },
+
+    #[error("Torrent {0} already exists")]
+    TorrentExists(String),
+
+    #[error("Invalid preferences: {}", fmt_list(.0))]
+    InvalidPreferences(Vec<InvalidPreference>),
+
+    #[cfg(any(feature = "toml", feature = "yaml"))]
+    #[error("Preferences file error: {0}")]
+    PreferencesFile(#[from] preferences_file::FileError),
+
+    #[error("{} of {total} batched requests failed", failures.len())]
+    BatchFailed {
+        total: usize,
+        failures: Vec<batch::BatchFailure>,
+    },
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(error: reqwest::Error) -> Self {
+        Self::HttpError {
+            error,
+            context: None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Self::SerdeJsonError {
+            error,
+            context: None,
+        }
+    }
+}
+
+impl From<ApiError> for Error {
+    fn from(error: ApiError) -> Self {
+        Self::ApiError {
+            error,
+            context: None,
+        }
+    }
+}
+
+impl Error {
+    /// Whether the [default](RetryPolicy::default) retry policy would send
+    /// the request again, e.g. after a connection failure or a `503`
+    /// response. See [`RetryPolicy::should_retry`].
+    pub fn is_retryable(&self) -> bool {
+        RetryPolicy::default().should_retry(self)
+    }
+
+    /// Whether the request failed because authentication was missing or
+    /// rejected, by qBittorrent or a reverse proxy in front of it
+    pub fn is_auth(&self) -> bool {
+        match self {
+            Self::ApiError { error, .. } => {
+                matches!(error, ApiError::IpBanned | ApiError::NotLoggedIn)
+            }
+            Self::UnknownHttpCode { status, .. } => *status == StatusCode::UNAUTHORIZED,
+            _ => false,
+        }
+    }
+
+    /// The request and response the error is about, if known
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Self::HttpError { context, .. }
+            | Self::ApiError { context, .. }
+            | Self::SerdeJsonError { context, .. }
+            | Self::BadResponse { context, .. }
+            | Self::UnknownHttpCode { context, .. } => context.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Attach `context` unless the error already has one
+    pub(crate) fn with_context(mut self, context: Option<Box<ErrorContext>>) -> Self {
+        match &mut self {
+            Self::HttpError { context: c, .. }
+            | Self::ApiError { context: c, .. }
+            | Self::SerdeJsonError { context: c, .. }
+            | Self::BadResponse { context: c, .. }
+            | Self::UnknownHttpCode { context: c, .. } => *c = c.take().or(context),
+            _ => {}
+        }
+        self
+    }
+}
+
+/// Request an [`Error`] is about, and the response to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorContext {
+    pub method: Method,
+    /// API path, relative to `api/v2/`
+    pub path: &'static str,
+    /// `None` if no response was received
+    pub status: Option<StatusCode>,
+    /// Start of the response body, at most [`ErrorContext::MAX_BODY`] bytes.
+    /// Only kept for unsuccessful or malformed responses.
+    pub body: Option<String>,
+}
+
+impl ErrorContext {
+    /// Maximum length of [`ErrorContext::body`]
+    pub const MAX_BODY: usize = 512;
+
+    fn new(method: Method, path: &'static str, status: Option<StatusCode>) -> Option<Box<Self>> {
+        Some(Box::new(Self {
+            method,
+            path,
+            status,
+            body: None,
+        }))
+    }
+
+    fn with_body(self, body: impl AsRef<[u8]>) -> Self {
+        let body = String::from_utf8_lossy(body.as_ref());
+        let body = body.trim();
+        let body = if body.len() > Self::MAX_BODY {
+            let end = (0..=Self::MAX_BODY)
+                .rev()
+                .find(|&i| body.is_char_boundary(i))
+                .unwrap_or(0);
+            format!("{}...", &body[..end])
+        } else {
+            body.to_owned()
+        };
 
-    #[error("serde_json error: {0}")]
-    SerdeJsonError(#[from] serde_json::Error),
+        Self {
+            body: Some(body).filter(|b| !b.is_empty()),
+            ..self
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.method, self.path)?;
+        if let Some(body) = &self.body {
+            write!(f, ": {body}")?;
+        }
+        Ok(())
+    }
+}
+
+fn fmt_context(context: &Option<Box<ErrorContext>>) -> String {
+    context
+        .as_ref()
+        .map(|c| format!(" ({c})"))
+        .unwrap_or_default()
+}
+
+fn fmt_list(items: &[impl std::fmt::Display]) -> String {
+    items
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 /// Errors defined and returned by the API
@@ -1351,6 +2155,30 @@ pub enum ApiError {
 
     #[error("Invalid `newPath` or `oldPath`, or `newPath` already in use")]
     InvalidPath,
+
+    #[error("Torrent file is not valid")]
+    InvalidTorrent,
+
+    #[error("Torrent metadata hasn't downloaded yet")]
+    MetadataNotDownloaded,
+
+    #[error("None of the given tracker URLs were found")]
+    TrackerUrlNotFound,
+
+    #[error("File priority or file id is not valid")]
+    InvalidFilePriority,
+
+    #[error("Category name is empty")]
+    CategoryNameEmpty,
+
+    #[error("Category name is not valid")]
+    CategoryNameInvalid,
+
+    #[error("Directory path is empty or not absolute")]
+    InvalidDirectoryPath,
+
+    #[error("Directory does not exist")]
+    DirectoryNotFound,
 }
 
 type Result<T, E = Error> = std::result::Result<T, E>;
@@ -1366,6 +2194,7 @@ mod test {
     use tracing::info;
 
     use super::*;
+    use crate::mock::{self, serve, MockResponse};
 
     async fn prepare<'a>() -> Result<&'a Qbit> {
         static PREPARE: LazyLock<(Credential, Url)> = LazyLock::new(|| {
@@ -1396,6 +2225,646 @@ mod test {
         }
     }
 
+    #[tokio::test]
+    async fn test_dry_run() {
+        let client = Qbit::builder()
+            .endpoint("http://localhost:8080")
+            .credential(Credential::dummy())
+            .dry_run(true)
+            .build();
+
+        client
+            .delete_torrents(vec!["abc".to_owned()], true)
+            .await
+            .unwrap();
+        client.pause_torrents(Hashes::All).await.unwrap();
+
+        assert_eq!(
+            client.take_planned_actions(),
+            vec![
+                PlannedAction {
+                    path: "torrents/delete",
+                    body: "hashes=abc&deleteFiles=true".to_owned(),
+                },
+                PlannedAction {
+                    path: "torrents/pause",
+                    body: "hashes=all".to_owned(),
+                },
+            ]
+        );
+
+        client
+            .edit_trackers(
+                "abc",
+                "http://a.example/announce".parse().unwrap(),
+                "http://b.example/announce".parse().unwrap(),
+            )
+            .await
+            .unwrap();
+        client.toggle_speed_limits_mode().await.unwrap();
+        client.logout().await.unwrap();
+        let paths = client
+            .take_planned_actions()
+            .into_iter()
+            .map(|a| a.path)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                "torrents/editTracker",
+                "transfer/toggleSpeedLimitsMode",
+                "auth/logout"
+            ]
+        );
+        assert!(client.take_planned_actions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_login_single_flight() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let logins = Arc::new(AtomicUsize::new(0));
+        let counter = logins.clone();
+        let (url, requests) = serve(move |req| match req.path.as_str() {
+            "/api/v2/auth/login" => {
+                let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                MockResponse::ok("Ok.").header("set-cookie", format!("SID={n}"))
+            }
+            _ if req.header("cookie") == Some("SID=1") => MockResponse::status(403),
+            _ => MockResponse::ok("v4.6.0"),
+        })
+        .await;
+
+        let changes = Arc::new(Mutex::new(vec![]));
+        let log = changes.clone();
+        let client = Qbit::builder()
+            .endpoint(url)
+            .credential(Credential::new("admin", "adminadmin"))
+            .on_cookie_change(move |cookie| log.lock().unwrap().push(cookie.to_owned()))
+            .build();
+
+        let versions = futures_util::future::join_all((0..8).map(|_| client.get_version())).await;
+        for version in versions {
+            assert_eq!(version.unwrap(), "v4.6.0");
+        }
+
+        assert_eq!(logins.load(Ordering::SeqCst), 2);
+        assert_eq!(*changes.lock().unwrap(), ["SID=1", "SID=2"]);
+        assert_eq!(client.get_cookie().await.as_deref(), Some("SID=2"));
+
+        assert_eq!(requests.paths().len(), 2 + 8 * 2);
+        assert_eq!(
+            requests.posted("/api/v2/auth/login")[0],
+            "username=admin&password=adminadmin"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_login_timeout() {
+        // Accept connections but never answer them
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut streams = vec![];
+            while let Ok((stream, _)) = listener.accept().await {
+                streams.push(stream);
+            }
+        });
+
+        let client = Qbit::builder()
+            .endpoint(format!("http://{addr}").as_str())
+            .credential(Credential::new("admin", "adminadmin"))
+            .timeout(Duration::from_millis(100))
+            .build();
+        let err = tokio::time::timeout(Duration::from_secs(5), client.get_version())
+            .await
+            .expect("login should time out")
+            .unwrap_err();
+        let Error::HttpError { error, context } = &err else {
+            panic!("unexpected error: {err}");
+        };
+        assert!(error.is_timeout());
+        assert_eq!(context.as_ref().unwrap().path, "auth/login");
+    }
+
+    #[tokio::test]
+    async fn test_forbidden_after_login() {
+        let (url, requests) = serve(|req| match req.path.as_str() {
+            "/api/v2/auth/login" => MockResponse::ok("Ok.").header("set-cookie", "SID=1"),
+            _ => MockResponse::status(403),
+        })
+        .await;
+        let client = Qbit::builder()
+            .endpoint(url)
+            .credential(Credential::new("admin", "adminadmin"))
+            .build();
+
+        let err = client
+            .set_torrent_location(Hashes::All, "/readonly")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::ApiError {
+                error: ApiError::NoWriteAccess,
+                ..
+            }
+        ));
+        assert_eq!(
+            requests.paths(),
+            [
+                "/api/v2/auth/login",
+                "/api/v2/torrents/setLocation",
+                "/api/v2/auth/login",
+                "/api/v2/torrents/setLocation"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_session_store() {
+        use crate::session::MemorySessionStore;
+
+        let (url, requests) = serve(|req| match req.path.as_str() {
+            "/api/v2/auth/login" => MockResponse::ok("Ok.").header("set-cookie", "SID=new"),
+            _ if req.header("cookie") == Some("SID=new") => MockResponse::ok("v4.6.0"),
+            _ => MockResponse::status(403),
+        })
+        .await;
+
+        let store = Arc::new(MemorySessionStore::new());
+        let key = SessionKey::new(&url, "admin");
+        store.save(&key, "SID=old").unwrap();
+
+        let build = || {
+            Qbit::builder()
+                .endpoint(url.clone())
+                .credential(Credential::new("admin", "adminadmin"))
+                .session_store(store.clone())
+                .build()
+        };
+
+        assert_eq!(build().get_version().await.unwrap(), "v4.6.0");
+        assert_eq!(store.load(&key).unwrap().as_deref(), Some("SID=new"));
+        assert_eq!(build().get_version().await.unwrap(), "v4.6.0");
+
+        assert_eq!(
+            requests.paths(),
+            [
+                "/api/v2/app/version",
+                "/api/v2/auth/login",
+                "/api/v2/app/version",
+                "/api/v2/app/version",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_proxy_headers() {
+        let (url, requests) = serve(|req| match req.path.as_str() {
+            "/api/v2/auth/login" => MockResponse::ok("Ok.").header("set-cookie", "SID=1"),
+            _ => MockResponse::ok("v4.6.0"),
+        })
+        .await;
+        let origin = url.origin().ascii_serialization();
+
+        let client = Qbit::builder()
+            .endpoint(url.clone())
+            .credential(Credential::new("admin", "adminadmin"))
+            .basic_auth("proxy", Some("secret"))
+            .default_header(
+                header::HeaderName::from_static("x-forwarded-host"),
+                header::HeaderValue::from_static("qbit.example.com"),
+            )
+            .origin_headers(true)
+            .build();
+        assert_eq!(client.get_version().await.unwrap(), "v4.6.0");
+
+        let requests = requests.all();
+        assert_eq!(requests.len(), 2);
+        for req in requests {
+            assert_eq!(req.header("authorization"), Some("Basic cHJveHk6c2VjcmV0"));
+            assert_eq!(req.header("x-forwarded-host"), Some("qbit.example.com"));
+            assert_eq!(req.header("origin"), Some(origin.as_str()));
+            assert_eq!(req.header("referer"), Some(url.as_str()));
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_socket() {
+        use crate::mock::serve_unix;
+
+        let path = std::env::temp_dir().join(format!("qbit-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let requests = serve_unix(&path, |req| match req.path.as_str() {
+            "/qbittorrent/api/v2/app/version" => MockResponse::ok("v4.6.0"),
+            _ => MockResponse::status(404),
+        })
+        .await;
+
+        let client = Qbit::builder()
+            .endpoint("http://qbittorrent.local/qbittorrent")
+            .cookie("SID=1")
+            .unix_socket(&path)
+            .build();
+        assert_eq!(client.get_version().await.unwrap(), "v4.6.0");
+        assert_eq!(requests.all()[0].header("host"), Some("qbittorrent.local"));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_error_context() {
+        let (url, _) = serve(|req| match req.path.as_str() {
+            "/api/v2/app/version" => MockResponse::status(500).body("x".repeat(1000)),
+            "/api/v2/app/webapiVersion" => MockResponse::status(503),
+            "/api/v2/app/preferences" => MockResponse::ok(r#"{"listen_port":"x"}"#),
+            "/api/v2/torrents/add" => MockResponse::status(415),
+            "/api/v2/transfer/downloadLimit" => MockResponse::ok("unlimited"),
+            _ => MockResponse::status(401),
+        })
+        .await;
+        let client = Qbit::builder()
+            .endpoint(url)
+            .cookie("SID=1")
+            .retry_policy(RetryPolicy::none())
+            .build();
+
+        let err = client.get_version().await.unwrap_err();
+        assert!(matches!(err, Error::UnknownHttpCode { status, .. } if status == 500));
+        assert!(!err.is_retryable());
+        let context = err.context().unwrap();
+        assert_eq!(context.method, Method::GET);
+        assert_eq!(context.path, "app/version");
+        assert_eq!(context.status, Some(StatusCode::INTERNAL_SERVER_ERROR));
+        assert_eq!(
+            context.body.as_deref().map(str::len),
+            Some(ErrorContext::MAX_BODY + 3)
+        );
+
+        let err = client.get_download_limit().await.unwrap_err();
+        assert!(matches!(err, Error::BadResponse { .. }));
+        assert_eq!(err.context().unwrap().body.as_deref(), Some("unlimited"));
+
+        let err = client.get_preferences().await.unwrap_err();
+        assert!(matches!(err, Error::SerdeJsonError { .. }));
+        let context = err.context().unwrap();
+        assert_eq!(context.path, "app/preferences");
+        assert_eq!(context.status, Some(StatusCode::OK));
+        assert_eq!(context.body.as_deref(), Some(r#"{"listen_port":"x"}"#));
+
+        let err = client
+            .add_torrent(AddTorrentArg::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::ApiError {
+                error: ApiError::InvalidTorrent,
+                ..
+            }
+        ));
+        assert!(!err.is_retryable());
+        assert_eq!(
+            err.to_string(),
+            "Torrent file is not valid (POST torrents/add)"
+        );
+
+        let err = client.get_webapi_version().await.unwrap_err();
+        assert!(err.is_retryable());
+        assert!(RetryPolicy::default().should_retry(&err));
+        assert!(!RetryPolicy::none().should_retry(&err));
+
+        let err = client.get_build_info().await.unwrap_err();
+        assert!(err.is_auth());
+        assert_eq!(
+            err.to_string(),
+            "API returned unknown status code: 401 Unauthorized (GET app/buildInfo)"
+        );
+
+        // Nothing listens on port 1
+        let client = Qbit::builder()
+            .endpoint("http://127.0.0.1:1")
+            .cookie("SID=1")
+            .retry_policy(RetryPolicy::none())
+            .build();
+        let err = client.get_version().await.unwrap_err();
+        assert!(matches!(err, Error::HttpError { .. }));
+        assert!(err.is_retryable());
+        let context = err.context().unwrap();
+        assert_eq!(context.path, "app/version");
+        assert_eq!(context.status, None);
+    }
+
+    #[tokio::test]
+    async fn test_torrents_paged_mock() {
+        let (url, requests) = serve(|req| {
+            let query = req.path.split_once('?').map_or("", |(_, q)| q);
+            let param = |name: &str| {
+                url::form_urlencoded::parse(query.as_bytes())
+                    .find(|(k, _)| k == name)
+                    .map(|(_, v)| v.parse::<usize>().unwrap())
+            };
+            let (offset, limit) = (param("offset").unwrap(), param("limit").unwrap());
+            let page = (0..5)
+                .skip(offset)
+                .take(limit)
+                .map(|i| format!(r#"{{"hash":"{i}"}}"#))
+                .collect::<Vec<_>>();
+            MockResponse::ok(format!("[{}]", page.join(",")))
+        })
+        .await;
+        let client = mock::client(url);
+        let hashes = |arg: GetTorrentListArg, page_size| {
+            let client = &client;
+            async move {
+                client
+                    .torrents_paged(arg, page_size)
+                    .map_ok(|t| t.hash.unwrap())
+                    .try_collect::<Vec<_>>()
+                    .await
+                    .unwrap()
+            }
+        };
+        let pages = || {
+            requests
+                .take()
+                .into_iter()
+                .map(|r| r.path.split_once('?').unwrap().1.to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        // The last page is short
+        assert_eq!(
+            hashes(GetTorrentListArg::default(), 2).await,
+            ["0", "1", "2", "3", "4"]
+        );
+        assert_eq!(
+            pages(),
+            [
+                "sort=hash&limit=2&offset=0",
+                "sort=hash&limit=2&offset=2",
+                "sort=hash&limit=2&offset=4"
+            ]
+        );
+
+        // A full last page needs one more request to find the end
+        assert_eq!(hashes(GetTorrentListArg::default(), 5).await.len(), 5);
+        assert_eq!(pages().len(), 2);
+
+        // `limit` and `offset` bound the range
+        let arg = GetTorrentListArg {
+            limit: Some(3),
+            offset: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(hashes(arg, 2).await, ["1", "2", "3"]);
+        assert_eq!(
+            pages(),
+            ["sort=hash&limit=2&offset=1", "sort=hash&limit=1&offset=3"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_toggle_not_retried() {
+        let (url, requests) = serve(|_| MockResponse::status(503)).await;
+        let client = Qbit::builder()
+            .endpoint(url)
+            .cookie("SID=1")
+            .retry_policy(RetryPolicy {
+                initial_backoff: Duration::ZERO,
+                ..RetryPolicy::default()
+            })
+            .build();
+
+        let err = client.toggle_speed_limits_mode().await.unwrap_err();
+        assert!(matches!(err, Error::UnknownHttpCode { status, .. } if status == 503));
+        assert!(!err.is_retryable());
+        assert_eq!(requests.paths().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_apply_preferences() {
+        let (url, requests) = serve(|req| match req.path.as_str() {
+            "/api/v2/app/preferences" => MockResponse::ok(r#"{"locale":"en","listen_port":6881}"#),
+            _ => MockResponse::ok(""),
+        })
+        .await;
+        let client = mock::client(url);
+
+        let desired = Preferences {
+            locale: Some("en".to_owned()),
+            listen_port: Some(51413),
+            ..Default::default()
+        };
+        let patch = client.apply_preferences(&desired).await.unwrap();
+        assert_eq!(
+            patch.iter().map(|(k, _)| k).collect::<Vec<_>>(),
+            ["listen_port"]
+        );
+
+        let patch = client
+            .apply_preferences(Preferences {
+                listen_port: Some(6881),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert!(patch.is_empty());
+
+        assert_eq!(requests.posted_paths(), ["/api/v2/app/setPreferences"]);
+        assert_eq!(
+            requests.posted("/api/v2/app/setPreferences"),
+            ["json=%7B%22listen_port%22%3A51413%7D"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_speed_limits_mode() {
+        let (url, requests) = serve(|req| match req.path.as_str() {
+            "/api/v2/transfer/speedLimitsMode" => MockResponse::ok("1"),
+            _ => MockResponse::ok(""),
+        })
+        .await;
+        let client = mock::client(url);
+
+        client.set_speed_limits_mode(true).await.unwrap();
+        client.set_speed_limits_mode(false).await.unwrap();
+        let toggles = requests.posted("/api/v2/transfer/toggleSpeedLimitsMode");
+        assert_eq!(toggles.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_set_speed_limits_mode_dry_run() {
+        let (url, requests) = serve(|req| match req.path.as_str() {
+            "/api/v2/transfer/speedLimitsMode" => MockResponse::ok("0"),
+            _ => MockResponse::ok(""),
+        })
+        .await;
+        let client = Qbit::builder()
+            .endpoint(url)
+            .cookie("SID=1")
+            .dry_run(true)
+            .build();
+
+        client.set_speed_limits_mode(true).await.unwrap();
+        assert_eq!(
+            client.take_planned_actions(),
+            vec![PlannedAction {
+                path: "transfer/toggleSpeedLimitsMode",
+                body: String::new(),
+            }]
+        );
+        assert_eq!(requests.paths(), ["/api/v2/transfer/speedLimitsMode"]);
+    }
+
+    #[tokio::test]
+    async fn test_set_speed_limits_mode_failed_toggle() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let enabled = Arc::new(AtomicBool::new(false));
+        let failed = Arc::new(AtomicBool::new(false));
+        let (mode, fail) = (enabled.clone(), failed.clone());
+        let (url, requests) = serve(move |req| match req.path.as_str() {
+            "/api/v2/transfer/speedLimitsMode" => {
+                MockResponse::ok(if mode.load(Ordering::SeqCst) {
+                    "1"
+                } else {
+                    "0"
+                })
+            }
+            "/api/v2/transfer/toggleSpeedLimitsMode" => {
+                if fail.swap(true, Ordering::SeqCst) {
+                    mode.fetch_xor(true, Ordering::SeqCst);
+                    MockResponse::ok("")
+                } else {
+                    MockResponse::status(503)
+                }
+            }
+            _ => MockResponse::ok(""),
+        })
+        .await;
+        let client = Qbit::builder()
+            .endpoint(url)
+            .cookie("SID=1")
+            .retry_policy(RetryPolicy {
+                initial_backoff: Duration::ZERO,
+                ..RetryPolicy::default()
+            })
+            .build();
+
+        client.set_speed_limits_mode(true).await.unwrap_err();
+        assert!(!enabled.load(Ordering::SeqCst));
+        client.set_speed_limits_mode(true).await.unwrap();
+        client.set_speed_limits_mode(true).await.unwrap();
+        assert!(enabled.load(Ordering::SeqCst));
+
+        let toggles = requests.posted("/api/v2/transfer/toggleSpeedLimitsMode");
+        assert_eq!(toggles.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_bind_network_interface() {
+        let (url, requests) = serve(|req| match req.path.as_str() {
+            "/api/v2/app/networkInterfaceList" => MockResponse::ok(
+                r#"[{"name":"eth0","value":"eth0"},{"name":"WireGuard","value":"wg0"}]"#,
+            ),
+            "/api/v2/app/networkInterfaceAddressList?iface=wg0" => {
+                MockResponse::ok(r#"["10.8.0.2","fd00::2"]"#)
+            }
+            _ => MockResponse::ok(""),
+        })
+        .await;
+        let client = mock::client(url);
+
+        assert_eq!(
+            client.get_network_interfaces().await.unwrap()[1],
+            NetworkInterface {
+                name: "WireGuard".to_owned(),
+                value: "wg0".to_owned()
+            }
+        );
+        assert!(matches!(
+            client.bind_network_interface("tun0", None).await,
+            Err(Error::InvalidPreferences(_))
+        ));
+        assert!(matches!(
+            client.bind_network_interface("wg0", "10.8.0.3").await,
+            Err(Error::InvalidPreferences(e)) if e[0].field == "current_interface_address"
+        ));
+        client
+            .bind_network_interface("WireGuard", "10.8.0.2")
+            .await
+            .unwrap();
+
+        assert_eq!(requests.posted_paths(), ["/api/v2/app/setPreferences"]);
+        assert_eq!(
+            requests.posted("/api/v2/app/setPreferences"),
+            [
+                "json=%7B%22current_interface_address%22%3A%2210.8.0.2%22%2C%\
+                 22current_network_interface%22%3A%22wg0%22%2C%22current_interface_name%22%3A%\
+                 22WireGuard%22%7D"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_auth_strategies() {
+        let (url, requests) = serve(|req| {
+            if req.header("x-proxy-token") != Some("secret") {
+                return MockResponse::status(401);
+            }
+            match req.path.as_str() {
+                "/api/v2/auth/login" => MockResponse::ok("Ok.").header("set-cookie", "SID=1"),
+                _ if req.header("authorization") == Some("Bearer key") => {
+                    MockResponse::ok("v5.1.0")
+                }
+                _ if req.header("cookie") == Some("SID=1") => MockResponse::ok("v4.6.0"),
+                _ => MockResponse::status(403),
+            }
+        })
+        .await;
+        let proxy_token = |req: RequestBuilder| req.header("x-proxy-token", "secret");
+
+        let client = Qbit::builder()
+            .endpoint(url.clone())
+            .api_key("key")
+            .auth_hook(proxy_token)
+            .build();
+        assert_eq!(client.get_version().await.unwrap(), "v5.1.0");
+
+        let client = Qbit::builder()
+            .endpoint(url.clone())
+            .api_key("wrong")
+            .auth_hook(proxy_token)
+            .build();
+        assert!(matches!(
+            client.get_version().await,
+            Err(Error::ApiError {
+                error: ApiError::NotLoggedIn,
+                ..
+            })
+        ));
+
+        let client = Qbit::builder()
+            .endpoint(url)
+            .credential(Credential::new("admin", "adminadmin"))
+            .auth_hook(proxy_token)
+            .build();
+        assert_eq!(client.get_version().await.unwrap(), "v4.6.0");
+
+        assert_eq!(
+            requests.paths(),
+            [
+                "/api/v2/app/version",
+                "/api/v2/app/version",
+                "/api/v2/auth/login",
+                "/api/v2/app/version",
+            ]
+        );
+    }
+
     #[tokio::test]
     async fn test_login() {
         let client = prepare().await.unwrap();
@@ -1430,6 +2899,21 @@ mod test {
         client.add_torrent(arg).await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_torrents_paged() {
+        let client = prepare().await.unwrap();
+        let all = client
+            .get_torrent_list(GetTorrentListArg::default())
+            .await
+            .unwrap();
+        let paged = client
+            .torrents_paged(GetTorrentListArg::default(), 2)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(all.len(), paged.len());
+    }
+
     #[tokio::test]
     async fn test_get_torrent_list() {
         let client = prepare().await.unwrap();
@@ -0,0 +1,124 @@
+//! Client-side rate limiting, enforced on every request sent through
+//! [`Qbit`](crate::Qbit).
+
+use std::{
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+use tokio::{
+    sync::{Semaphore, SemaphorePermit},
+    time::{sleep_until, Instant},
+};
+use tracing::debug;
+
+/// Limits on requests sent to the server. Set with
+/// [`QbitBuilder::rate_limit`](crate::QbitBuilder::rate_limit).
+///
+/// qBittorrent's WebUI is single-threaded, so flooding it with requests makes
+/// the UI unresponsive for everyone else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RateLimit {
+    /// Maximum number of requests started per second
+    pub requests_per_second: Option<u32>,
+    /// Maximum number of requests waiting for a response at the same time.
+    ///
+    /// A request stops counting once the status and headers of its response
+    /// arrived, so downloading large bodies, e.g. exported .torrent files or
+    /// full sync data, is not limited.
+    pub max_in_flight: Option<usize>,
+}
+
+impl RateLimit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn requests_per_second(self, requests_per_second: u32) -> Self {
+        Self {
+            requests_per_second: Some(requests_per_second),
+            ..self
+        }
+    }
+
+    pub fn max_in_flight(self, max_in_flight: usize) -> Self {
+        Self {
+            max_in_flight: Some(max_in_flight),
+            ..self
+        }
+    }
+}
+
+/// Runtime state of a [`RateLimit`]
+#[derive(Debug)]
+pub(crate) struct Limiter {
+    interval: Option<Duration>,
+    next: Mutex<Option<Instant>>,
+    in_flight: Option<Semaphore>,
+}
+
+impl Limiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self {
+            interval: limit
+                .requests_per_second
+                .filter(|&rps| rps > 0)
+                .map(|rps| Duration::from_secs(1) / rps),
+            next: Mutex::new(None),
+            in_flight: limit.max_in_flight.map(|n| Semaphore::new(n.max(1))),
+        }
+    }
+
+    /// Wait until a request may be sent. The request counts as in flight until
+    /// the returned permit is dropped.
+    pub(crate) async fn acquire(&self, path: &str) -> Option<SemaphorePermit<'_>> {
+        if self.interval.is_none() && self.in_flight.is_none() {
+            return None;
+        }
+        let start = Instant::now();
+
+        // `acquire` only fails once the semaphore is closed, which it never is
+        let permit = match &self.in_flight {
+            Some(semaphore) => semaphore.acquire().await.ok(),
+            None => None,
+        };
+
+        if let Some(interval) = self.interval {
+            let slot = {
+                let mut next = self.next.lock().unwrap_or_else(PoisonError::into_inner);
+                let slot = next.map_or(start, |next| next.max(Instant::now()));
+                *next = Some(slot + interval);
+                slot
+            };
+            sleep_until(slot).await;
+        }
+
+        let wait = start.elapsed();
+        debug!(
+            path,
+            wait_ms = wait.as_secs_f64() * 1000.0,
+            "Waited for rate limiter"
+        );
+
+        permit
+    }
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_limiter() {
+    let limiter = Limiter::new(RateLimit::new().requests_per_second(10).max_in_flight(2));
+    let start = Instant::now();
+
+    for _ in 0..5 {
+        drop(limiter.acquire("app/version").await);
+    }
+    assert!(start.elapsed() >= Duration::from_millis(400));
+
+    let a = limiter.acquire("app/version").await;
+    let _b = limiter.acquire("app/version").await;
+    let semaphore = limiter.in_flight.as_ref().unwrap();
+    assert_eq!(semaphore.available_permits(), 0);
+    drop(a);
+    assert_eq!(semaphore.available_permits(), 1);
+}
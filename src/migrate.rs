@@ -0,0 +1,355 @@
+//! Moving torrents between qBittorrent instances.
+//!
+//! ```rust,ignore
+//! use qbit_rs::migrate::{migrate_torrent, MigrateOptions};
+//!
+//! migrate_torrent(&old, &new, hash, &MigrateOptions::default()).await?;
+//! ```
+
+use std::time::Duration;
+
+use tokio::time::{sleep, Instant};
+use tracing::{debug, info, warn};
+
+use crate::{
+    model::{AddTorrentArg, GetTorrentListArg, NonEmptyStr, State, Torrent, TorrentSource},
+    ApiError, Error, Qbit, Result,
+};
+
+/// How [`migrate_torrent`] adds the torrent to the target instance
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrateOptions {
+    /// Skip hash checking on the target. Only safe if the target sees the
+    /// same files at the same save path.
+    pub skip_checking: bool,
+    /// Add the torrent in the paused state. Defaults to whether it is paused
+    /// on the source.
+    pub paused: Option<bool>,
+    /// Save path on the target, if it differs from the one on the source
+    pub save_path: Option<String>,
+    /// How long to wait for the torrent to appear on the target
+    pub timeout: Duration,
+    /// Delay between two checks whether the torrent appeared on the target
+    pub poll_interval: Duration,
+}
+
+impl Default for MigrateOptions {
+    fn default() -> Self {
+        Self {
+            skip_checking: true,
+            paused: None,
+            save_path: None,
+            timeout: Duration::from_secs(30),
+            poll_interval: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Move the torrent `hash` from `from` to `to`, keeping its save path,
+/// category, tags and limits.
+///
+/// The .torrent file is exported from `from` and added to `to`. Once it
+/// appears there, its tags are added and it is deleted from `from`, leaving
+/// the files in place. If a step fails while the torrent is still on `from`,
+/// it is removed from `to` again, so that it ends up on exactly one instance.
+/// If deleting from `from` fails and it cannot be told whether the torrent is
+/// still there, it is left on both.
+///
+/// Automatic Torrent Management is disabled on the target, since it would
+/// move the files to the category's save path.
+pub async fn migrate_torrent(
+    from: &Qbit,
+    to: &Qbit,
+    hash: impl AsRef<str> + Send + Sync,
+    options: &MigrateOptions,
+) -> Result<()> {
+    let hash = hash.as_ref();
+    let torrent = find(from, hash)
+        .await?
+        .ok_or(Error::from(ApiError::TorrentNotFound))?;
+    if find(to, hash).await?.is_some() {
+        return Err(Error::TorrentExists(hash.to_owned()));
+    }
+
+    let properties = from.get_torrent_properties(hash).await?;
+    let data = from.export_torrent(hash).await?;
+    debug!(hash, size = data.len(), "Exported torrent");
+
+    let category = torrent.category.clone().filter(|c| !c.is_empty());
+    if let Some(category) = &category {
+        ensure_category(from, to, category).await?;
+    }
+
+    let added = to.add_torrent(AddTorrentArg {
+        source: TorrentSource::TorrentFiles { torrents: data },
+        savepath: options
+            .save_path
+            .clone()
+            .or(properties.save_path)
+            .or(torrent.save_path.clone()),
+        category,
+        skip_checking: Some(options.skip_checking.to_string()),
+        paused: Some(
+            options
+                .paused
+                .unwrap_or_else(|| torrent.state.as_ref().is_some_and(State::is_paused))
+                .to_string(),
+        ),
+        up_limit: torrent.up_limit,
+        download_limit: torrent.dl_limit,
+        ratio_limit: torrent.ratio_limit,
+        seeding_time_limit: torrent.seeding_time_limit,
+        auto_torrent_management: Some(false),
+        ..Default::default()
+    });
+    if let Err(e) = added.await {
+        // The server may have added the torrent before the request failed
+        if let Ok(Some(_)) = find(to, hash).await {
+            rollback(to, hash, &e).await;
+        }
+        return Err(e);
+    }
+
+    if let Err(e) = finish(to, hash, &torrent, options).await {
+        rollback(to, hash, &e).await;
+        return Err(e);
+    }
+
+    if let Err(e) = from.delete_torrents(vec![hash.to_owned()], false).await {
+        match find(from, hash).await {
+            Ok(None) => warn!(hash, error = %e, "Torrent deleted from source despite error"),
+            Ok(Some(_)) => {
+                rollback(to, hash, &e).await;
+                return Err(e);
+            }
+            Err(error) => {
+                warn!(hash, %error, "Torrent may be left on both source and target");
+                return Err(e);
+            }
+        }
+    }
+
+    info!(hash, "Migrated torrent");
+    Ok(())
+}
+
+/// Remove the torrent from the target after the migration failed with `error`
+async fn rollback(to: &Qbit, hash: &str, error: &Error) {
+    warn!(hash, %error, "Migration failed, removing torrent from target");
+    if let Err(error) = to.delete_torrents(vec![hash.to_owned()], false).await {
+        warn!(hash, %error, "Failed to roll back migration");
+    }
+}
+
+/// Steps after the torrent was added to the target, before it is deleted from
+/// the source
+async fn finish(to: &Qbit, hash: &str, torrent: &Torrent, options: &MigrateOptions) -> Result<()> {
+    wait_for_torrent(to, hash, options.timeout, options.poll_interval).await?;
+
+    let tags = torrent
+        .tags
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>()
+        .join(",");
+    if !tags.is_empty() {
+        to.add_torrent_tags(vec![hash.to_owned()], vec![tags])
+            .await?;
+    }
+    Ok(())
+}
+
+pub(crate) async fn find(api: &Qbit, hash: &str) -> Result<Option<Torrent>> {
+    let arg = GetTorrentListArg {
+        hashes: Some(hash.to_owned()),
+        ..Default::default()
+    };
+    Ok(api.get_torrent_list(arg).await?.into_iter().next())
+}
+
+/// Wait until the torrent `hash`, which was just added, appears on `api`
+pub(crate) async fn wait_for_torrent(
+    api: &Qbit,
+    hash: &str,
+    timeout: Duration,
+    poll_interval: Duration,
+) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+    while find(api, hash).await?.is_none() {
+        if Instant::now() >= deadline {
+            return Err(Error::from(ApiError::TorrentNotFound));
+        }
+        sleep(poll_interval).await;
+    }
+    Ok(())
+}
+
+/// Create `category` on `to` with the save path it has on `from`, unless it
+/// already exists
+async fn ensure_category(from: &Qbit, to: &Qbit, category: &str) -> Result<()> {
+    if to.get_categories().await?.contains_key(category) {
+        return Ok(());
+    }
+    let save_path = from
+        .get_categories()
+        .await?
+        .remove(category)
+        .map(|c| c.save_path)
+        .unwrap_or_default();
+    let name = NonEmptyStr::new(category).ok_or(Error::from(ApiError::CategoryNameEmpty))?;
+    to.add_category(name, save_path).await
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_migrate_torrent() {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    use crate::mock::{self, serve, MockResponse};
+
+    let torrent = r#"[{"hash":"abc","category":"","tags":"a, b","save_path":"/data","up_limit":1024,"state":"pausedUP"}]"#;
+    // `fail` is the path answered with 500, after applying the request if
+    // `applied`
+    let instance = |present: bool, fail: &'static str, applied: bool| async move {
+        let present = Arc::new(AtomicBool::new(present));
+        serve(move |req| {
+            let path = req.path.as_str();
+            if path.starts_with("/api/v2/torrents/info") {
+                return if present.load(Ordering::SeqCst) {
+                    MockResponse::ok(torrent)
+                } else {
+                    MockResponse::ok("[]")
+                };
+            }
+            let result = match path {
+                "/api/v2/torrents/add" => Some(true),
+                "/api/v2/torrents/delete" => Some(false),
+                _ => None,
+            };
+            if path == fail {
+                if let Some(result) = result.filter(|_| applied) {
+                    present.store(result, Ordering::SeqCst);
+                }
+                return MockResponse::status(500);
+            }
+            if let Some(result) = result {
+                present.store(result, Ordering::SeqCst);
+            }
+            match path {
+                p if p.starts_with("/api/v2/torrents/properties") => {
+                    MockResponse::ok(r#"{"save_path":"/data"}"#)
+                }
+                p if p.starts_with("/api/v2/torrents/export") => MockResponse::ok("d4:infoe"),
+                "/api/v2/torrents/add" => MockResponse::ok("Ok."),
+                _ => MockResponse::ok(""),
+            }
+        })
+        .await
+    };
+    let options = MigrateOptions {
+        poll_interval: Duration::from_millis(10),
+        ..Default::default()
+    };
+    let migrate = |source_fail, source_applied, target_fail, target_applied| {
+        let options = options.clone();
+        async move {
+            let (source_url, source) = instance(true, source_fail, source_applied).await;
+            let (target_url, target) = instance(false, target_fail, target_applied).await;
+            let res = migrate_torrent(
+                &mock::client(source_url),
+                &mock::client(target_url),
+                "abc",
+                &options,
+            )
+            .await;
+            (res, source.posted_paths(), target.posted_paths())
+        }
+    };
+
+    // Adding tags fails, the torrent is removed from the target
+    let (res, source, target) = migrate("", false, "/api/v2/torrents/addTags", false).await;
+    assert!(matches!(res, Err(Error::UnknownHttpCode { .. })));
+    assert!(source.is_empty());
+    assert_eq!(
+        target,
+        [
+            "/api/v2/torrents/add",
+            "/api/v2/torrents/addTags",
+            "/api/v2/torrents/delete"
+        ]
+    );
+
+    // Adding fails after the server added the torrent
+    let (res, source, target) = migrate("", false, "/api/v2/torrents/add", true).await;
+    assert!(res.is_err());
+    assert!(source.is_empty());
+    assert_eq!(target, ["/api/v2/torrents/add", "/api/v2/torrents/delete"]);
+
+    // Adding fails and the server did not add the torrent
+    let (res, _, target) = migrate("", false, "/api/v2/torrents/add", false).await;
+    assert!(res.is_err());
+    assert_eq!(target, ["/api/v2/torrents/add"]);
+
+    // Deleting from the source fails and the torrent is still there
+    let (res, source, target) = migrate("/api/v2/torrents/delete", false, "", false).await;
+    assert!(res.is_err());
+    assert_eq!(source, ["/api/v2/torrents/delete"]);
+    assert_eq!(
+        target,
+        [
+            "/api/v2/torrents/add",
+            "/api/v2/torrents/addTags",
+            "/api/v2/torrents/delete"
+        ]
+    );
+
+    // Deleting from the source fails after the torrent was deleted
+    let (res, source, target) = migrate("/api/v2/torrents/delete", true, "", false).await;
+    res.unwrap();
+    assert_eq!(source, ["/api/v2/torrents/delete"]);
+    assert_eq!(target, ["/api/v2/torrents/add", "/api/v2/torrents/addTags"]);
+
+    let (source_url, source) = instance(true, "", false).await;
+    let (url, requests) = instance(false, "", false).await;
+    migrate_torrent(
+        &mock::client(source_url),
+        &mock::client(url),
+        "abc",
+        &options,
+    )
+    .await
+    .unwrap();
+    let add = requests.posted("/api/v2/torrents/add").concat();
+    assert!(add.contains("d4:infoe"));
+    assert!(add.contains("/data"));
+    // Paused like on the source
+    assert!(add.contains("name=\"paused\"\r\n\r\ntrue\r\n"));
+    assert_eq!(
+        requests.posted("/api/v2/torrents/addTags"),
+        ["hashes=abc&tags=a%2Cb"]
+    );
+    assert_eq!(source.posted_paths(), ["/api/v2/torrents/delete"]);
+
+    let (source_url, _) = instance(true, "", false).await;
+    let (url, requests) = instance(false, "", false).await;
+    let options = MigrateOptions {
+        paused: Some(false),
+        ..options
+    };
+    migrate_torrent(
+        &mock::client(source_url),
+        &mock::client(url),
+        "abc",
+        &options,
+    )
+    .await
+    .unwrap();
+    let add = requests.posted("/api/v2/torrents/add").concat();
+    assert!(add.contains("name=\"paused\"\r\n\r\nfalse\r\n"));
+}
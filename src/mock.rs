@@ -0,0 +1,231 @@
+//! Minimal HTTP server used to test request handling without a qBittorrent
+//! instance.
+
+use std::sync::{Arc, Mutex};
+
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpListener,
+};
+use url::Url;
+
+use crate::Qbit;
+
+#[derive(Debug, Clone)]
+pub(crate) struct MockRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl MockRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl MockResponse {
+    pub fn ok(body: impl Into<String>) -> Self {
+        Self {
+            status: 200,
+            headers: vec![],
+            body: body.into(),
+        }
+    }
+
+    pub fn status(status: u16) -> Self {
+        Self {
+            status,
+            ..Self::ok("")
+        }
+    }
+
+    pub fn body(self, body: impl Into<String>) -> Self {
+        Self {
+            body: body.into(),
+            ..self
+        }
+    }
+
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+}
+
+/// Log of the requests received by a mock server
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Requests(Arc<Mutex<Vec<MockRequest>>>);
+
+impl Requests {
+    fn push(&self, req: MockRequest) {
+        self.0.lock().unwrap().push(req);
+    }
+
+    pub fn all(&self) -> Vec<MockRequest> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Remove the requests received so far from the log and return them
+    pub fn take(&self) -> Vec<MockRequest> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+
+    /// Paths of all requests, including the query string
+    pub fn paths(&self) -> Vec<String> {
+        self.all().into_iter().map(|r| r.path).collect()
+    }
+
+    /// Paths of the `POST` requests
+    pub fn posted_paths(&self) -> Vec<String> {
+        self.all()
+            .into_iter()
+            .filter(|r| r.method == "POST")
+            .map(|r| r.path)
+            .collect()
+    }
+
+    /// Bodies of the `POST` requests to `path`
+    pub fn posted(&self, path: &str) -> Vec<String> {
+        self.all()
+            .into_iter()
+            .filter(|r| r.method == "POST" && r.path == path)
+            .map(|r| r.body)
+            .collect()
+    }
+}
+
+/// Client for the mock server at `url`, already logged in
+pub(crate) fn client(url: Url) -> Qbit {
+    Qbit::builder().endpoint(url).cookie("SID=1").build()
+}
+
+/// Serve `handler` on a random local port. Returns the endpoint and the log of
+/// received requests.
+pub(crate) async fn serve<F>(handler: F) -> (Url, Requests)
+where
+    F: Fn(&MockRequest) -> MockResponse + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap())
+        .parse()
+        .unwrap();
+    let requests = Requests::default();
+    let handler = Arc::new(handler);
+
+    let log = requests.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let handler = handler.clone();
+            let log = log.clone();
+            tokio::spawn(async move {
+                let mut stream = stream;
+                read_request(&mut stream, handler.as_ref(), &log).await;
+            });
+        }
+    });
+
+    (url, requests)
+}
+
+/// Serve `handler` on a Unix domain socket at `path`. Returns the log of
+/// received requests.
+#[cfg(unix)]
+pub(crate) async fn serve_unix<F>(path: &std::path::Path, handler: F) -> Requests
+where
+    F: Fn(&MockRequest) -> MockResponse + Send + Sync + 'static,
+{
+    let listener = tokio::net::UnixListener::bind(path).unwrap();
+    let requests = Requests::default();
+    let handler = Arc::new(handler);
+
+    let log = requests.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let handler = handler.clone();
+            let log = log.clone();
+            tokio::spawn(async move {
+                let mut stream = stream;
+                read_request(&mut stream, handler.as_ref(), &log).await;
+            });
+        }
+    });
+
+    requests
+}
+
+async fn read_request<S, F>(stream: &mut S, handler: &F, log: &Requests) -> Option<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    F: Fn(&MockRequest) -> MockResponse + Sync,
+{
+    let mut buf = vec![];
+    let head_len = loop {
+        let mut chunk = [0; 4096];
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..head_len]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split(' ');
+    let method = request_line.next()?.to_owned();
+    let path = request_line.next()?.to_owned();
+    let headers = lines
+        .filter_map(|l| l.split_once(':'))
+        .map(|(k, v)| (k.trim().to_owned(), v.trim().to_owned()))
+        .collect::<Vec<_>>();
+
+    let len = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, v)| v.parse::<usize>().ok())
+        .unwrap_or(0);
+    while buf.len() < head_len + len {
+        let mut chunk = [0; 4096];
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let req = MockRequest {
+        method,
+        path,
+        headers,
+        body: String::from_utf8_lossy(&buf[head_len..]).into_owned(),
+    };
+    let res = handler(&req);
+    log.push(req);
+
+    let mut out = format!(
+        "HTTP/1.1 {} Mock\r\ncontent-length: {}\r\nconnection: close\r\n",
+        res.status,
+        res.body.len()
+    );
+    for (k, v) in &res.headers {
+        out.push_str(&format!("{k}: {v}\r\n"));
+    }
+    out.push_str("\r\n");
+    out.push_str(&res.body);
+    stream.write_all(out.as_bytes()).await.ok()?;
+    stream.shutdown().await.ok()
+}
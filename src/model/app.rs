@@ -1,9 +1,14 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    path::PathBuf,
+};
 
 use serde::{de::Visitor, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
 use serde_with::skip_serializing_none;
 
-use crate::model::IntOrStr;
+use crate::model::MaybeKnown;
 
 #[derive(Debug, Clone, serde::Deserialize, PartialEq, Eq)]
 pub struct BuildInfo {
@@ -19,13 +24,37 @@ pub struct BuildInfo {
     bitness: i8,
 }
 
+/// Entries returned by
+/// [`Qbit::get_directory_content`](crate::Qbit::get_directory_content)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DirectoryContentMode {
+    /// Directories and files
+    All,
+    /// Only directories
+    Dirs,
+    /// Only files
+    Files,
+}
+
+/// A network interface, as listed by
+/// [`Qbit::get_network_interfaces`](crate::Qbit::get_network_interfaces)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkInterface {
+    /// Human readable name
+    pub name: String,
+    /// Identifier of the interface, as used by
+    /// [`Preferences::current_network_interface`]
+    pub value: String,
+}
+
+#[skip_serializing_none]
 #[cfg_attr(feature = "builder", derive(typed_builder::TypedBuilder))]
 #[cfg_attr(
     feature = "builder",
     builder(field_defaults(default, setter(strip_option)))
 )]
 #[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize, PartialEq)]
-#[skip_serializing_none]
 pub struct Preferences {
     /// Currently selected language (e.g. en_GB for English)
     pub locale: Option<String>,
@@ -33,8 +62,8 @@ pub struct Preferences {
     pub create_subfolder_enabled: Option<bool>,
     /// True if torrents should be added in a Paused state
     pub start_paused_enabled: Option<bool>,
-    /// TODO
-    pub auto_delete_mode: Option<i64>,
+    /// Whether .torrent files are deleted after they were added
+    pub auto_delete_mode: Option<MaybeKnown<AutoDeleteMode>>,
     /// True if disk space should be pre-allocated for all files
     pub preallocate_all: Option<bool>,
     /// True if ".!qB" should be appended to incomplete files
@@ -109,9 +138,8 @@ pub struct Preferences {
     pub max_ratio_enabled: Option<bool>,
     /// Get the global share ratio limit
     pub max_ratio: Option<f64>,
-    /// Action performed when a torrent reaches the maximum share ratio. See
-    /// list of possible values here below.
-    pub max_ratio_act: Option<i64>,
+    /// Action performed when a torrent reaches the maximum share ratio
+    pub max_ratio_act: Option<MaybeKnown<MaxRatioAction>>,
     /// Port for incoming connections
     pub listen_port: Option<i64>,
     /// True if UPnP/NAT-PMP is enabled
@@ -135,8 +163,8 @@ pub struct Preferences {
     /// True if the advanced libtorrent option `piece_extent_affinity` is
     /// enabled
     pub enable_piece_extent_affinity: Option<bool>,
-    /// Bittorrent Protocol to use (see list of possible values below)
-    pub bittorrent_protocol: Option<i64>,
+    /// Bittorrent Protocol to use
+    pub bittorrent_protocol: Option<MaybeKnown<BittorrentProtocol>>,
     /// True if `[du]l_limit` should be applied to uTP connections; this option
     /// is only available in qBittorent built against libtorrent version 0.16.X
     /// and higher
@@ -160,23 +188,23 @@ pub struct Preferences {
     pub schedule_to_hour: Option<i64>,
     /// Scheduler ending minute
     pub schedule_to_min: Option<i64>,
-    /// Scheduler days. See possible values here below
-    pub scheduler_days: Option<i64>,
+    /// Scheduler days
+    pub scheduler_days: Option<MaybeKnown<SchedulerDays>>,
     /// True if DHT is enabled
     pub dht: Option<bool>,
     /// True if PeX is enabled
     pub pex: Option<bool>,
     /// True if LSD is enabled
     pub lsd: Option<bool>,
-    /// See list of possible values here below
-    pub encryption: Option<i64>,
+    /// Whether connections to peers are encrypted
+    pub encryption: Option<MaybeKnown<Encryption>>,
     /// If true anonymous mode will be enabled; read more
     /// [here](Anonymous-Mode); this option is only available in qBittorent
     /// built against libtorrent version 0.16.X and higher
     pub anonymous_mode: Option<bool>,
-    /// In old versions (before 4.6.1 or early), this returns an interger. In
-    /// newer versions, this returns a string.
-    pub proxy_type: Option<IntOrStr>,
+    /// Proxy type, reported as an integer before qBittorrent 4.6.1 and as a
+    /// string since then
+    pub proxy_type: Option<ProxyType>,
     /// Proxy IP address or domain name
     pub proxy_ip: Option<String>,
     /// Proxy port
@@ -255,8 +283,8 @@ pub struct Preferences {
     pub web_ui_https_cert_path: Option<String>,
     /// True if server DNS should be updated dynamically
     pub dyndns_enabled: Option<bool>,
-    /// See list of possible values here below
-    pub dyndns_service: Option<i64>,
+    /// Dynamic DNS service
+    pub dyndns_service: Option<MaybeKnown<DyndnsService>>,
     /// Username for DDNS service
     pub dyndns_username: Option<String>,
     /// Password for DDNS service
@@ -340,14 +368,395 @@ pub struct Preferences {
     pub send_buffer_watermark_factor: Option<i64>,
     /// Socket backlog size
     pub socket_backlog_size: Option<i64>,
-    /// Upload choking algorithm used (see list of possible values below)
-    pub upload_choking_algorithm: Option<i64>,
-    /// Upload slots behavior used (see list of possible values below)
-    pub upload_slots_behavior: Option<i64>,
+    /// Upload choking algorithm used
+    pub upload_choking_algorithm: Option<MaybeKnown<UploadChokingAlgorithm>>,
+    /// Upload slots behavior used
+    pub upload_slots_behavior: Option<MaybeKnown<UploadSlotsBehavior>>,
     /// UPnP lease duration (0: Permanent lease)
     pub upnp_lease_duration: Option<i64>,
-    /// μTP-TCP mixed mode algorithm (see list of possible values below)
-    pub utp_tcp_mixed_mode: Option<i64>,
+    /// μTP-TCP mixed mode algorithm
+    pub utp_tcp_mixed_mode: Option<MaybeKnown<UtpTcpMixedMode>>,
+    /// Layout of the content of added torrents
+    pub torrent_content_layout: Option<MaybeKnown<TorrentContentLayout, String>>,
+    /// True if torrents should be added in the stopped state (qBittorrent ≥
+    /// 5.0, replaces `start_paused_enabled`)
+    pub add_stopped_enabled: Option<bool>,
+    /// True if torrents should be added to the top of the queue
+    pub add_to_top_of_queue: Option<bool>,
+    /// When added torrents are stopped
+    pub torrent_stop_condition: Option<MaybeKnown<TorrentStopCondition, String>>,
+    /// True if trackers of a torrent that is added again should be merged
+    /// into the existing one
+    pub merge_trackers: Option<bool>,
+    /// True if subcategories are enabled
+    pub use_subcategories: Option<bool>,
+    /// True if category paths are used in manual mode
+    pub use_category_paths_in_manual_mode: Option<bool>,
+    /// True if files that are not downloaded are kept in a `.unwanted` folder
+    pub use_unwanted_folder: Option<bool>,
+    /// True if files matching `excluded_file_names` are not downloaded
+    pub excluded_file_names_enabled: Option<bool>,
+    /// Newline separated list of file name patterns that are not downloaded
+    pub excluded_file_names: Option<String>,
+    /// True if an external program should be run when a torrent is added
+    pub autorun_on_torrent_added_enabled: Option<bool>,
+    /// Program to run when a torrent is added, see `autorun_program`
+    pub autorun_on_torrent_added_program: Option<String>,
+    /// Maximum number of torrents checked simultaneously
+    pub max_active_checking_torrents: Option<i64>,
+    /// True if the inactive seeding time limit is enabled
+    pub max_inactive_seeding_time_enabled: Option<bool>,
+    /// Inactive seeding time limit in minutes
+    pub max_inactive_seeding_time: Option<i64>,
+    /// True if trackers listed at `add_trackers_url` should be added to new
+    /// torrents
+    pub add_trackers_from_url_enabled: Option<bool>,
+    /// URL of a list of trackers added to new torrents
+    pub add_trackers_url: Option<String>,
+    /// Trackers last fetched from `add_trackers_url`
+    pub add_trackers_url_list: Option<String>,
+    /// True if the proxy is used for peer connections
+    pub proxy_bittorrent: Option<bool>,
+    /// True if the proxy is used for RSS
+    pub proxy_rss: Option<bool>,
+    /// True if the proxy is used for other purposes, e.g. search and updates
+    pub proxy_misc: Option<bool>,
+    /// True if host names are resolved through the proxy
+    pub proxy_hostname_lookup: Option<bool>,
+    /// True if I2P is enabled
+    pub i2p_enabled: Option<bool>,
+    /// Address of the I2P SAM bridge
+    pub i2p_address: Option<String>,
+    /// Port of the I2P SAM bridge
+    pub i2p_port: Option<i64>,
+    /// True if peers are also connected to outside of I2P
+    pub i2p_mixed_mode: Option<bool>,
+    /// Number of I2P inbound tunnels
+    pub i2p_inbound_quantity: Option<i64>,
+    /// Number of I2P outbound tunnels
+    pub i2p_outbound_quantity: Option<i64>,
+    /// Length of I2P inbound tunnels
+    pub i2p_inbound_length: Option<i64>,
+    /// Length of I2P outbound tunnels
+    pub i2p_outbound_length: Option<i64>,
+    /// Delay in seconds between two fetches of the same RSS feed
+    pub rss_fetch_delay: Option<i64>,
+    /// True if the WebUI is behind a reverse proxy
+    pub web_ui_reverse_proxy_enabled: Option<bool>,
+    /// Semicolon separated list of trusted reverse proxies
+    pub web_ui_reverse_proxies_list: Option<String>,
+    /// True if logging to a file is enabled
+    pub file_log_enabled: Option<bool>,
+    /// Directory of the log file
+    pub file_log_path: Option<String>,
+    /// True if the log file is backed up once it reaches `file_log_max_size`
+    pub file_log_backup_enabled: Option<bool>,
+    /// Maximum size of the log file in KiB
+    pub file_log_max_size: Option<i64>,
+    /// True if old log files are deleted
+    pub file_log_delete_old: Option<bool>,
+    /// Age after which old log files are deleted, in `file_log_age_type` units
+    pub file_log_age: Option<i64>,
+    /// Unit of `file_log_age`
+    pub file_log_age_type: Option<MaybeKnown<FileLogAgeType>>,
+    /// True if performance warnings of libtorrent are logged
+    pub performance_warning: Option<bool>,
+    /// Name of the instance, shown in the WebUI title
+    pub app_instance_name: Option<String>,
+    /// Refresh interval of the UI in milliseconds
+    pub refresh_interval: Option<i64>,
+    /// True if the external IP is shown in the status bar
+    pub status_bar_external_ip: Option<bool>,
+    /// How resume data is stored
+    pub resume_data_storage_type: Option<MaybeKnown<ResumeDataStorageType, String>>,
+    /// What happens to the files of a removed torrent
+    pub torrent_content_remove_option: Option<MaybeKnown<TorrentContentRemoveOption, String>>,
+    /// Physical memory usage limit in MiB
+    pub memory_working_set_limit: Option<i64>,
+    /// Name of the network interface to bind to
+    pub current_interface_name: Option<String>,
+    /// Interval in minutes between two saves of the statistics
+    pub save_statistics_interval: Option<i64>,
+    /// Maximum size of a .torrent file in bytes
+    pub torrent_file_size_limit: Option<i64>,
+    /// True if trackers are reannounced when the IP address or port changes
+    pub reannounce_when_address_changed: Option<bool>,
+    /// True if the port of the embedded tracker is forwarded with UPnP
+    pub embedded_tracker_port_forwarding: Option<bool>,
+    /// True if downloaded files are marked as coming from the internet
+    pub mark_of_the_web: Option<bool>,
+    /// True if SSL errors are ignored when downloading .torrent files and RSS
+    /// feeds
+    pub ignore_ssl_errors: Option<bool>,
+    /// Path to the Python executable used by search plugins
+    pub python_executable_path: Option<String>,
+    /// Maximum depth of bencoded data
+    pub bdecode_depth_limit: Option<i64>,
+    /// Maximum number of tokens in bencoded data
+    pub bdecode_token_limit: Option<i64>,
+    /// Number of threads used to hash pieces
+    pub hashing_threads: Option<i64>,
+    /// Disk queue size in bytes
+    pub disk_queue_size: Option<i64>,
+    /// Disk IO type
+    pub disk_io_type: Option<MaybeKnown<DiskIoType>>,
+    /// Disk IO read mode
+    pub disk_io_read_mode: Option<MaybeKnown<DiskIoReadMode>>,
+    /// Disk IO write mode
+    pub disk_io_write_mode: Option<MaybeKnown<DiskIoWriteMode>>,
+    /// Maximum number of outgoing connections attempted per second
+    pub connection_speed: Option<i64>,
+    /// Socket send buffer size in bytes, `0` for the system default
+    pub socket_send_buffer_size: Option<i64>,
+    /// Socket receive buffer size in bytes, `0` for the system default
+    pub socket_receive_buffer_size: Option<i64>,
+    /// Type of service value of peer connections
+    pub peer_tos: Option<i64>,
+    /// True if internationalized domain names are supported
+    pub idn_support_enabled: Option<bool>,
+    /// True if certificates of HTTPS trackers are validated
+    pub validate_https_tracker_certificate: Option<bool>,
+    /// True if server-side request forgery mitigation is enabled
+    pub ssrf_mitigation: Option<bool>,
+    /// True if connections to peers on privileged ports are disallowed
+    pub block_peers_on_privileged_ports: Option<bool>,
+    /// Port reported to trackers, `0` for the listening port
+    pub announce_port: Option<i64>,
+    /// Maximum number of concurrent HTTP announces
+    pub max_concurrent_http_announces: Option<i64>,
+    /// Maximum number of outstanding requests to a peer
+    pub request_queue_size: Option<i64>,
+    /// Percentage of peers disconnected on peer turnover
+    pub peer_turnover: Option<i64>,
+    /// Percentage of the connection limit above which peer turnover happens
+    pub peer_turnover_cutoff: Option<i64>,
+    /// Interval of peer turnover in seconds
+    pub peer_turnover_interval: Option<i64>,
+    /// Comma separated list of DHT bootstrap nodes
+    pub dht_bootstrap_nodes: Option<String>,
+    /// Fields not known to this version of the library. They are kept, so
+    /// that a get/modify/set round trip does not drop them.
+    #[serde(flatten)]
+    #[cfg_attr(feature = "builder", builder(setter(!strip_option)))]
+    pub extra: Map<String, Value>,
+}
+
+impl Preferences {
+    /// Fields set in `desired` whose value differs from `self`. Fields that
+    /// are `None` in `desired` are left out.
+    pub fn diff(&self, desired: &Preferences) -> PreferencesPatch {
+        let current = to_map(self);
+        let changes = to_map(desired)
+            .into_iter()
+            .filter(|(_, new)| !new.is_null())
+            .filter_map(|(name, new)| {
+                let old = current.get(&name).filter(|v| !v.is_null()).cloned();
+                (old.as_ref() != Some(&new)).then_some((name, PreferenceChange { old, new }))
+            })
+            .collect();
+
+        PreferencesPatch { changes }
+    }
+
+    /// Check the fields that are set: ports have to be in range and enum-like
+    /// fields have to hold a value known to qBittorrent.
+    pub fn validate(&self) -> Result<(), Vec<InvalidPreference>> {
+        let mut errors = vec![];
+        let mut check = |field: &'static str, valid: bool, message: String| {
+            if !valid {
+                errors.push(InvalidPreference { field, message });
+            }
+        };
+
+        for (field, port) in [
+            ("listen_port", self.listen_port),
+            ("proxy_port", self.proxy_port),
+            ("web_ui_port", self.web_ui_port),
+            ("embedded_tracker_port", self.embedded_tracker_port),
+            ("i2p_port", self.i2p_port),
+        ] {
+            if let Some(port) = port {
+                check(
+                    field,
+                    (1..=65535).contains(&port),
+                    format!("port {port} is not in 1..=65535"),
+                );
+            }
+        }
+        // 0 disables the restriction of outgoing ports, or reports the listening
+        // port to trackers
+        for (field, port) in [
+            ("outgoing_ports_min", self.outgoing_ports_min),
+            ("outgoing_ports_max", self.outgoing_ports_max),
+            ("announce_port", self.announce_port),
+        ] {
+            if let Some(port) = port {
+                check(
+                    field,
+                    (0..=65535).contains(&port),
+                    format!("port {port} is not in 0..=65535"),
+                );
+            }
+        }
+        if let (Some(min @ 1..), Some(max @ 1..)) =
+            (self.outgoing_ports_min, self.outgoing_ports_max)
+        {
+            check(
+                "outgoing_ports_min",
+                min <= max,
+                format!("{min} is greater than outgoing_ports_max {max}"),
+            );
+        }
+
+        for (field, value, max) in [
+            ("schedule_from_hour", self.schedule_from_hour, 23),
+            ("schedule_from_min", self.schedule_from_min, 59),
+            ("schedule_to_hour", self.schedule_to_hour, 23),
+            ("schedule_to_min", self.schedule_to_min, 59),
+        ] {
+            if let Some(value) = value {
+                check(
+                    field,
+                    (0..=max).contains(&value),
+                    format!("{value} is not in 0..={max}"),
+                );
+            }
+        }
+
+        for (field, unknown) in [
+            ("auto_delete_mode", unknown(&self.auto_delete_mode)),
+            ("max_ratio_act", unknown(&self.max_ratio_act)),
+            ("bittorrent_protocol", unknown(&self.bittorrent_protocol)),
+            ("scheduler_days", unknown(&self.scheduler_days)),
+            ("encryption", unknown(&self.encryption)),
+            ("dyndns_service", unknown(&self.dyndns_service)),
+            (
+                "upload_choking_algorithm",
+                unknown(&self.upload_choking_algorithm),
+            ),
+            (
+                "upload_slots_behavior",
+                unknown(&self.upload_slots_behavior),
+            ),
+            ("utp_tcp_mixed_mode", unknown(&self.utp_tcp_mixed_mode)),
+            (
+                "torrent_content_layout",
+                unknown(&self.torrent_content_layout),
+            ),
+            (
+                "torrent_stop_condition",
+                unknown(&self.torrent_stop_condition),
+            ),
+            ("file_log_age_type", unknown(&self.file_log_age_type)),
+            (
+                "resume_data_storage_type",
+                unknown(&self.resume_data_storage_type),
+            ),
+            (
+                "torrent_content_remove_option",
+                unknown(&self.torrent_content_remove_option),
+            ),
+            ("disk_io_type", unknown(&self.disk_io_type)),
+            ("disk_io_read_mode", unknown(&self.disk_io_read_mode)),
+            ("disk_io_write_mode", unknown(&self.disk_io_write_mode)),
+            (
+                "proxy_type",
+                self.proxy_type.as_ref().and_then(ProxyType::unknown),
+            ),
+        ] {
+            if let Some(value) = unknown {
+                check(field, false, format!("unknown value {value}"));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn unknown<T, U: Display>(value: &Option<MaybeKnown<T, U>>) -> Option<String> {
+    match value {
+        Some(MaybeKnown::Unknown(value)) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// A field rejected by [`Preferences::validate`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidPreference {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl Display for InvalidPreference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+fn to_map(preferences: &Preferences) -> Map<String, Value> {
+    match serde_json::to_value(preferences) {
+        Ok(Value::Object(map)) => map,
+        _ => Map::new(),
+    }
+}
+
+/// Changed fields of [`Preferences`], computed with [`Preferences::diff`] and
+/// sent with [`Qbit::set_preferences_patch`](crate::Qbit::set_preferences_patch)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PreferencesPatch {
+    changes: BTreeMap<String, PreferenceChange>,
+}
+
+/// Change of a single field in a [`PreferencesPatch`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreferenceChange {
+    /// Current value, `None` if the server did not report the field
+    pub old: Option<Value>,
+    pub new: Value,
+}
+
+impl PreferencesPatch {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn get(&self, field: &str) -> Option<&PreferenceChange> {
+        self.changes.get(field)
+    }
+
+    /// Changed fields, sorted by name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &PreferenceChange)> {
+        self.changes.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// JSON object with the new values, as expected by `app/setPreferences`
+    pub fn to_json(&self) -> Value {
+        self.changes
+            .iter()
+            .map(|(k, v)| (k.clone(), v.new.clone()))
+            .collect::<Map<_, _>>()
+            .into()
+    }
+}
+
+impl Display for PreferencesPatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (field, change) in self.iter() {
+            match &change.old {
+                Some(old) => writeln!(f, "{field}: {old} -> {}", change.new)?,
+                None => writeln!(f, "{field}: {}", change.new)?,
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -365,7 +774,13 @@ impl Serialize for ScanDirValue {
         match self {
             ScanDirValue::MonitoredFolder => serializer.serialize_i64(0),
             ScanDirValue::DefaultSavingPath => serializer.serialize_i64(1),
-            ScanDirValue::Path(path) => serializer.serialize_str(path.to_str().unwrap()),
+            ScanDirValue::Path(path) => match path.to_str() {
+                Some(path) => serializer.serialize_str(path),
+                None => Err(serde::ser::Error::custom(format!(
+                    "Scan dir path is not valid UTF-8: {}",
+                    path.display()
+                ))),
+            },
         }
     }
 }
@@ -406,6 +821,13 @@ impl Visitor<'_> for ScanDirsVisitor {
         }
     }
 
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        self.visit_i64(v.try_into().map_err(E::custom)?)
+    }
+
     fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
     where
         E: serde::de::Error,
@@ -413,3 +835,564 @@ impl Visitor<'_> for ScanDirsVisitor {
         Ok(ScanDirValue::Path(PathBuf::from(v)))
     }
 }
+
+/// Possible values of [`Preferences::auto_delete_mode`]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde_repr::Serialize_repr,
+    serde_repr::Deserialize_repr,
+)]
+#[repr(u8)]
+pub enum AutoDeleteMode {
+    /// Keep .torrent files
+    Never   = 0,
+    /// Delete .torrent files of torrents that were added
+    IfAdded = 1,
+    /// Delete .torrent files, even if adding the torrent was cancelled
+    Always  = 2,
+}
+
+/// Possible values of [`Preferences::max_ratio_act`]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde_repr::Serialize_repr,
+    serde_repr::Deserialize_repr,
+)]
+#[repr(u8)]
+pub enum MaxRatioAction {
+    /// Pause (stop) the torrent
+    Pause              = 0,
+    /// Remove the torrent
+    Remove             = 1,
+    /// Enable super seeding for the torrent
+    EnableSuperSeeding = 2,
+    /// Remove the torrent and its files
+    DeleteFiles        = 3,
+}
+
+/// Possible values of [`Preferences::bittorrent_protocol`]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde_repr::Serialize_repr,
+    serde_repr::Deserialize_repr,
+)]
+#[repr(u8)]
+pub enum BittorrentProtocol {
+    /// TCP and μTP
+    Both = 0,
+    /// TCP only
+    Tcp  = 1,
+    /// μTP only
+    Utp  = 2,
+}
+
+/// Possible values of [`Preferences::scheduler_days`]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde_repr::Serialize_repr,
+    serde_repr::Deserialize_repr,
+)]
+#[repr(u8)]
+pub enum SchedulerDays {
+    /// Every day
+    EveryDay  = 0,
+    /// Every weekday
+    Weekday   = 1,
+    /// Every weekend
+    Weekend   = 2,
+    /// Every Monday
+    Monday    = 3,
+    /// Every Tuesday
+    Tuesday   = 4,
+    /// Every Wednesday
+    Wednesday = 5,
+    /// Every Thursday
+    Thursday  = 6,
+    /// Every Friday
+    Friday    = 7,
+    /// Every Saturday
+    Saturday  = 8,
+    /// Every Sunday
+    Sunday    = 9,
+}
+
+/// Possible values of [`Preferences::encryption`]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde_repr::Serialize_repr,
+    serde_repr::Deserialize_repr,
+)]
+#[repr(u8)]
+pub enum Encryption {
+    /// Prefer encryption
+    Prefer   = 0,
+    /// Force encryption on
+    ForceOn  = 1,
+    /// Force encryption off
+    ForceOff = 2,
+}
+
+/// Possible values of [`Preferences::dyndns_service`]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde_repr::Serialize_repr,
+    serde_repr::Deserialize_repr,
+)]
+#[repr(u8)]
+pub enum DyndnsService {
+    /// Use DyDNS
+    DynDns = 0,
+    /// Use NOIP
+    NoIp   = 1,
+}
+
+/// Possible values of [`Preferences::upload_choking_algorithm`]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde_repr::Serialize_repr,
+    serde_repr::Deserialize_repr,
+)]
+#[repr(u8)]
+pub enum UploadChokingAlgorithm {
+    /// Round-robin
+    RoundRobin    = 0,
+    /// Fastest upload
+    FastestUpload = 1,
+    /// Anti-leech
+    AntiLeech     = 2,
+}
+
+/// Possible values of [`Preferences::upload_slots_behavior`]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde_repr::Serialize_repr,
+    serde_repr::Deserialize_repr,
+)]
+#[repr(u8)]
+pub enum UploadSlotsBehavior {
+    /// Fixed slots
+    FixedSlots = 0,
+    /// Upload rate based
+    RateBased  = 1,
+}
+
+/// Possible values of [`Preferences::utp_tcp_mixed_mode`]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde_repr::Serialize_repr,
+    serde_repr::Deserialize_repr,
+)]
+#[repr(u8)]
+pub enum UtpTcpMixedMode {
+    /// Prefer TCP
+    PreferTcp        = 0,
+    /// Peer proportional
+    PeerProportional = 1,
+}
+
+/// Possible values of [`Preferences::disk_io_type`]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde_repr::Serialize_repr,
+    serde_repr::Deserialize_repr,
+)]
+#[repr(u8)]
+pub enum DiskIoType {
+    /// Default of libtorrent
+    Default      = 0,
+    /// Memory mapped files
+    MemoryMapped = 1,
+    /// POSIX-compliant
+    Posix        = 2,
+}
+
+/// Possible values of [`Preferences::disk_io_read_mode`]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde_repr::Serialize_repr,
+    serde_repr::Deserialize_repr,
+)]
+#[repr(u8)]
+pub enum DiskIoReadMode {
+    /// Disable OS cache
+    DisableOsCache = 0,
+    /// Enable OS cache
+    EnableOsCache  = 1,
+}
+
+/// Possible values of [`Preferences::disk_io_write_mode`]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde_repr::Serialize_repr,
+    serde_repr::Deserialize_repr,
+)]
+#[repr(u8)]
+pub enum DiskIoWriteMode {
+    /// Disable OS cache
+    DisableOsCache = 0,
+    /// Enable OS cache
+    EnableOsCache  = 1,
+    /// Write-through
+    WriteThrough   = 2,
+}
+
+/// Possible values of [`Preferences::file_log_age_type`]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde_repr::Serialize_repr,
+    serde_repr::Deserialize_repr,
+)]
+#[repr(u8)]
+pub enum FileLogAgeType {
+    /// `file_log_age` is in days
+    Days   = 0,
+    /// `file_log_age` is in months
+    Months = 1,
+    /// `file_log_age` is in years
+    Years  = 2,
+}
+
+/// Possible values of [`Preferences::torrent_content_layout`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TorrentContentLayout {
+    /// Keep the layout of the torrent
+    Original,
+    /// Always create a subfolder
+    Subfolder,
+    /// Never create a subfolder
+    NoSubfolder,
+}
+
+/// Possible values of [`Preferences::torrent_stop_condition`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TorrentStopCondition {
+    /// Do not stop added torrents
+    None,
+    /// Stop once the metadata was received
+    MetadataReceived,
+    /// Stop once the files were checked
+    FilesChecked,
+}
+
+/// Possible values of [`Preferences::resume_data_storage_type`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ResumeDataStorageType {
+    /// Fastresume files
+    Legacy,
+    /// SQLite database
+    SQLite,
+}
+
+/// Possible values of [`Preferences::torrent_content_remove_option`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TorrentContentRemoveOption {
+    /// Delete the files permanently
+    Delete,
+    /// Move the files to the trash
+    MoveToTrash,
+}
+
+/// Value of [`Preferences::proxy_type`], in the representation used by the
+/// server. Write it back the way it was read, older servers do not accept
+/// [`ProxyType::Named`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ProxyType {
+    /// Reported since qBittorrent 4.6.1
+    Named(MaybeKnown<ProxyKind, String>),
+    /// Reported before qBittorrent 4.6.1
+    Legacy(MaybeKnown<LegacyProxyType>),
+}
+
+impl ProxyType {
+    /// The raw value, if it is unknown
+    pub fn unknown(&self) -> Option<String> {
+        match self {
+            Self::Named(MaybeKnown::Unknown(s)) => Some(format!("{s:?}")),
+            Self::Legacy(MaybeKnown::Unknown(i)) => Some(i.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// Proxy types reported as strings since qBittorrent 4.6.1. Whether the proxy
+/// requires authentication is a separate preference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ProxyKind {
+    None,
+    #[serde(rename = "HTTP")]
+    Http,
+    #[serde(rename = "SOCKS5")]
+    Socks5,
+    #[serde(rename = "SOCKS4")]
+    Socks4,
+}
+
+/// Proxy types reported as integers before qBittorrent 4.6.1
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde_repr::Serialize_repr,
+    serde_repr::Deserialize_repr,
+)]
+#[repr(i8)]
+pub enum LegacyProxyType {
+    /// Proxy is disabled
+    Disabled       = -1,
+    /// No proxy
+    None           = 0,
+    /// HTTP proxy without authentication
+    Http           = 1,
+    /// SOCKS5 proxy without authentication
+    Socks5         = 2,
+    /// HTTP proxy with authentication
+    HttpWithAuth   = 3,
+    /// SOCKS5 proxy with authentication
+    Socks5WithAuth = 4,
+    /// SOCKS4 proxy without authentication
+    Socks4         = 5,
+}
+
+#[test]
+fn test_preferences_diff() {
+    let current = Preferences {
+        locale: Some("en".to_owned()),
+        listen_port: Some(6881),
+        dht: Some(true),
+        ..Default::default()
+    };
+    let desired = Preferences {
+        locale: Some("en".to_owned()),
+        listen_port: Some(51413),
+        save_path: Some("/data".to_owned()),
+        ..Default::default()
+    };
+
+    let patch = current.diff(&desired);
+    assert_eq!(patch.len(), 2);
+    assert_eq!(
+        patch.get("listen_port"),
+        Some(&PreferenceChange {
+            old: Some(6881.into()),
+            new: 51413.into()
+        })
+    );
+    assert_eq!(patch.get("save_path").unwrap().old, None);
+    assert_eq!(
+        patch.to_json(),
+        serde_json::json!({ "listen_port": 51413, "save_path": "/data" })
+    );
+    assert_eq!(
+        patch.to_string(),
+        "listen_port: 6881 -> 51413\nsave_path: \"/data\"\n"
+    );
+    assert!(desired.diff(&desired).is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_scan_dir_value() {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = ScanDirValue::Path(PathBuf::from("/watch"));
+    assert_eq!(serde_json::to_value(&path).unwrap(), "/watch");
+    let path = ScanDirValue::Path(std::ffi::OsStr::from_bytes(b"/\xff").into());
+    assert!(serde_json::to_value(&path).is_err());
+}
+
+#[test]
+fn test_preferences_validate() {
+    let valid = Preferences {
+        listen_port: Some(51413),
+        outgoing_ports_min: Some(0),
+        encryption: Some(Encryption::ForceOn.into()),
+        proxy_type: Some(ProxyType::Named(ProxyKind::Socks5.into())),
+        ..Default::default()
+    };
+    assert_eq!(valid.validate(), Ok(()));
+
+    let invalid = Preferences {
+        listen_port: Some(70000),
+        outgoing_ports_min: Some(2000),
+        outgoing_ports_max: Some(1000),
+        encryption: Some(MaybeKnown::Unknown(3)),
+        upload_choking_algorithm: Some(MaybeKnown::Unknown(5)),
+        torrent_content_layout: Some(MaybeKnown::Unknown("Flat".to_owned())),
+        disk_io_type: Some(MaybeKnown::Unknown(9)),
+        proxy_type: Some(ProxyType::Legacy(MaybeKnown::Unknown(6))),
+        ..Default::default()
+    };
+    let fields = invalid
+        .validate()
+        .unwrap_err()
+        .into_iter()
+        .map(|e| e.field)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        fields,
+        [
+            "listen_port",
+            "outgoing_ports_min",
+            "encryption",
+            "upload_choking_algorithm",
+            "torrent_content_layout",
+            "disk_io_type",
+            "proxy_type"
+        ]
+    );
+}
+
+#[test]
+fn test_preferences_enums() {
+    let json = serde_json::json!({
+        "encryption": 1,
+        "max_ratio_act": 42,
+        "scheduler_days": 9,
+        "proxy_type": "SOCKS5",
+        "disk_io_type": 1,
+        "disk_io_write_mode": 2,
+        "file_log_age_type": 3,
+    });
+    let preferences = serde_json::from_value::<Preferences>(json.clone()).unwrap();
+    assert_eq!(preferences.encryption, Some(Encryption::ForceOn.into()));
+    assert_eq!(preferences.max_ratio_act, Some(MaybeKnown::Unknown(42)));
+    assert_eq!(
+        preferences.scheduler_days,
+        Some(SchedulerDays::Sunday.into())
+    );
+    assert_eq!(
+        preferences.proxy_type,
+        Some(ProxyType::Named(ProxyKind::Socks5.into()))
+    );
+    assert_eq!(
+        preferences.disk_io_type,
+        Some(DiskIoType::MemoryMapped.into())
+    );
+    assert_eq!(
+        preferences.disk_io_write_mode,
+        Some(DiskIoWriteMode::WriteThrough.into())
+    );
+    assert_eq!(preferences.file_log_age_type, Some(MaybeKnown::Unknown(3)));
+    assert_eq!(serde_json::to_value(&preferences).unwrap(), json);
+
+    let legacy = serde_json::from_str::<Preferences>(r#"{"proxy_type":-1}"#).unwrap();
+    assert_eq!(
+        legacy.proxy_type,
+        Some(ProxyType::Legacy(LegacyProxyType::Disabled.into()))
+    );
+    let unknown = serde_json::from_str::<Preferences>(r#"{"proxy_type":"SOCKS6"}"#).unwrap();
+    assert_eq!(
+        serde_json::to_string(&unknown).unwrap(),
+        r#"{"proxy_type":"SOCKS6"}"#
+    );
+}
+
+#[test]
+fn test_preferences_extra() {
+    let json = serde_json::json!({
+        "add_stopped_enabled": true,
+        "torrent_content_layout": "Subfolder",
+        "hashing_threads": 2,
+        "some_future_field": [1, 2],
+    });
+    let mut preferences = serde_json::from_value::<Preferences>(json.clone()).unwrap();
+    assert_eq!(preferences.add_stopped_enabled, Some(true));
+    assert_eq!(
+        preferences.torrent_content_layout,
+        Some(TorrentContentLayout::Subfolder.into())
+    );
+    assert_eq!(
+        preferences.extra,
+        Map::from_iter([("some_future_field".to_owned(), serde_json::json!([1, 2]))])
+    );
+    assert_eq!(serde_json::to_value(&preferences).unwrap(), json);
+
+    preferences.hashing_threads = Some(4);
+    let patch = serde_json::from_value::<Preferences>(json)
+        .unwrap()
+        .diff(&preferences);
+    assert_eq!(patch.to_json(), serde_json::json!({ "hashing_threads": 4 }));
+}
@@ -10,7 +10,7 @@ use serde::{Deserialize, Serialize};
 use serde_with::{DeserializeFromStr, SerializeDisplay};
 use tap::Pipe;
 
-mod_use::mod_use![app, log, sync, torrent, transfer, search];
+mod_use::mod_use![app, log, rss, schedule, sync, torrent, transfer, search];
 
 /// Username and password used to authenticate with qBittorrent.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -36,6 +36,10 @@ impl Credential {
         }
     }
 
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
     pub fn is_dummy(&self) -> bool {
         self.username.is_empty() && self.password.is_empty()
     }
@@ -106,11 +110,49 @@ pub enum IntOrStr {
     Str(String),
 }
 
-impl ToString for IntOrStr {
-    fn to_string(&self) -> String {
+impl Display for IntOrStr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IntOrStr::Int(i) => i.fmt(f),
+            IntOrStr::Str(s) => s.fmt(f),
+        }
+    }
+}
+
+/// A value of the enum `T`, or a raw value `U` unknown to this version of the
+/// library, e.g. one introduced by a newer version of qBittorrent. Unknown
+/// values are serialized back unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum MaybeKnown<T, U = i64> {
+    Known(T),
+    Unknown(U),
+}
+
+impl<T, U> MaybeKnown<T, U> {
+    pub fn known(&self) -> Option<&T> {
         match self {
-            IntOrStr::Int(i) => i.to_string(),
-            IntOrStr::Str(s) => s.clone(),
+            Self::Known(t) => Some(t),
+            Self::Unknown(_) => None,
+        }
+    }
+
+    pub fn is_known(&self) -> bool {
+        matches!(self, Self::Known(_))
+    }
+}
+
+impl<T, U> From<T> for MaybeKnown<T, U> {
+    fn from(t: T) -> Self {
+        Self::Known(t)
+    }
+}
+
+impl<T: std::fmt::Debug, U: Display> Display for MaybeKnown<T, U> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Known(t) => write!(f, "{t:?}"),
+            Self::Unknown(u) => write!(f, "unknown value {u}"),
         }
     }
 }
@@ -120,6 +162,16 @@ impl ToString for IntOrStr {
 #[derive(Debug, Clone, PartialEq, Eq, SerializeDisplay, DeserializeFromStr)]
 pub struct Sep<T, const C: char>(Vec<T>);
 
+impl<T, const C: char> Sep<T, C> {
+    pub fn as_slice(&self) -> &[T] {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> Vec<T> {
+        self.0
+    }
+}
+
 impl<T: FromStr, const C: char> FromStr for Sep<T, C> {
     type Err = T::Err;
 
@@ -0,0 +1,39 @@
+use serde_json::{Map, Value};
+use serde_with::skip_serializing_none;
+
+/// RSS auto-downloading rule, as returned by `rss/rules` and sent to
+/// `rss/setRule`
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RssRule {
+    /// Whether the rule is enabled
+    pub enabled: Option<bool>,
+    /// The substring that the torrent name must contain
+    pub must_contain: Option<String>,
+    /// The substring that the torrent name must not contain
+    pub must_not_contain: Option<String>,
+    /// Enable regex mode in `must_contain` and `must_not_contain`
+    pub use_regex: Option<bool>,
+    /// Episode filter definition
+    pub episode_filter: Option<String>,
+    /// Enable smart episode filter
+    pub smart_filter: Option<bool>,
+    /// The list of episode IDs already matched by smart filter
+    pub previously_matched_episodes: Option<Vec<String>>,
+    /// The feed URLs the rule applies to
+    pub affected_feeds: Option<Vec<String>>,
+    /// Ignore subsequent rule matches for this many days
+    pub ignore_days: Option<i64>,
+    /// The rule last match time
+    pub last_match: Option<String>,
+    /// Add matched torrent in paused mode
+    pub add_paused: Option<bool>,
+    /// Assign category to the torrent
+    pub assigned_category: Option<String>,
+    /// Save torrent to the given directory
+    pub save_path: Option<String>,
+    /// Fields added by newer versions of qBittorrent, e.g. `torrentParams`
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
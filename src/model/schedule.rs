@@ -0,0 +1,135 @@
+use std::fmt::Display;
+
+use crate::model::{MaybeKnown, Preferences, SchedulerDays};
+
+/// Time of day at which a [`SpeedSchedule`] starts or ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ScheduleTime {
+    hour: u8,
+    minute: u8,
+}
+
+impl ScheduleTime {
+    /// `None` unless `hour` is in `0..24` and `minute` in `0..60`
+    pub fn new(hour: u8, minute: u8) -> Option<Self> {
+        (hour < 24 && minute < 60).then_some(Self { hour, minute })
+    }
+
+    pub fn hour(&self) -> u8 {
+        self.hour
+    }
+
+    pub fn minute(&self) -> u8 {
+        self.minute
+    }
+
+    fn from_fields(hour: Option<i64>, minute: Option<i64>) -> Option<Self> {
+        Self::new(hour?.try_into().ok()?, minute?.try_into().ok()?)
+    }
+}
+
+impl Display for ScheduleTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute)
+    }
+}
+
+/// When the alternative speed limits are applied, and what they are.
+///
+/// Read from and written to the `scheduler_*`, `schedule_*` and `alt_*_limit`
+/// fields of [`Preferences`]. qBittorrent only supports the sets of days in
+/// [`SchedulerDays`]. A window whose end is before its start spans midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpeedSchedule {
+    /// Whether the schedule is active. If not, the alternative limits are
+    /// only applied by [`Qbit::set_speed_limits_mode`].
+    ///
+    /// [`Qbit::set_speed_limits_mode`]: crate::Qbit::set_speed_limits_mode
+    pub enabled: bool,
+    pub days: SchedulerDays,
+    pub from: ScheduleTime,
+    pub to: ScheduleTime,
+    /// Alternative download limit in KiB/s, `0` for no limit
+    pub alt_dl_limit: i64,
+    /// Alternative upload limit in KiB/s, `0` for no limit
+    pub alt_up_limit: i64,
+}
+
+impl SpeedSchedule {
+    /// Schedule described by `preferences`. `None` if a field is missing or
+    /// holds a value unknown to this library.
+    pub fn from_preferences(preferences: &Preferences) -> Option<Self> {
+        Some(Self {
+            enabled: preferences.scheduler_enabled?,
+            days: *preferences.scheduler_days.as_ref()?.known()?,
+            from: ScheduleTime::from_fields(
+                preferences.schedule_from_hour,
+                preferences.schedule_from_min,
+            )?,
+            to: ScheduleTime::from_fields(
+                preferences.schedule_to_hour,
+                preferences.schedule_to_min,
+            )?,
+            alt_dl_limit: preferences.alt_dl_limit?,
+            alt_up_limit: preferences.alt_up_limit?,
+        })
+    }
+
+    /// Set the fields of `preferences` describing the schedule, leaving the
+    /// others untouched
+    pub fn apply_to(&self, preferences: &mut Preferences) {
+        preferences.scheduler_enabled = Some(self.enabled);
+        preferences.scheduler_days = Some(MaybeKnown::Known(self.days));
+        preferences.schedule_from_hour = Some(self.from.hour.into());
+        preferences.schedule_from_min = Some(self.from.minute.into());
+        preferences.schedule_to_hour = Some(self.to.hour.into());
+        preferences.schedule_to_min = Some(self.to.minute.into());
+        preferences.alt_dl_limit = Some(self.alt_dl_limit);
+        preferences.alt_up_limit = Some(self.alt_up_limit);
+    }
+
+    /// Preferences with only the fields describing the schedule set
+    pub fn to_preferences(&self) -> Preferences {
+        let mut preferences = Preferences::default();
+        self.apply_to(&mut preferences);
+        preferences
+    }
+}
+
+#[test]
+fn test_speed_schedule() {
+    let schedule = SpeedSchedule {
+        enabled: true,
+        days: SchedulerDays::Weekday,
+        from: ScheduleTime::new(8, 30).unwrap(),
+        to: ScheduleTime::new(18, 0).unwrap(),
+        alt_dl_limit: 1024,
+        alt_up_limit: 0,
+    };
+    let preferences = schedule.to_preferences();
+    assert_eq!(
+        serde_json::to_value(&preferences).unwrap(),
+        serde_json::json!({
+            "alt_dl_limit": 1024,
+            "alt_up_limit": 0,
+            "scheduler_enabled": true,
+            "schedule_from_hour": 8,
+            "schedule_from_min": 30,
+            "schedule_to_hour": 18,
+            "schedule_to_min": 0,
+            "scheduler_days": 1,
+        })
+    );
+    assert_eq!(
+        SpeedSchedule::from_preferences(&preferences),
+        Some(schedule)
+    );
+    assert_eq!(schedule.from.to_string(), "08:30");
+
+    assert_eq!(ScheduleTime::new(24, 0), None);
+    let invalid = Preferences {
+        schedule_to_min: Some(60),
+        ..preferences
+    };
+    assert_eq!(SpeedSchedule::from_preferences(&invalid), None);
+}
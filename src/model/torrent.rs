@@ -1,8 +1,12 @@
-use std::fmt::{Debug, Display};
+use std::{
+    convert::Infallible,
+    fmt::{Debug, Display},
+    str::FromStr,
+};
 
 use reqwest::Url;
 use serde::Serialize;
-use serde_with::{skip_serializing_none, SerializeDisplay};
+use serde_with::{skip_serializing_none, DeserializeFromStr, SerializeDisplay};
 
 use crate::model::Sep;
 
@@ -137,6 +141,10 @@ pub enum State {
     /// Torrent is paused and has finished downloading
     #[serde(rename = "pausedUP")]
     PausedUP,
+    /// Torrent is stopped and has finished downloading. Replaces `pausedUP`
+    /// since qBittorrent 5.0.
+    #[serde(rename = "stoppedUP")]
+    StoppedUP,
     /// Queuing is enabled and torrent is queued for upload
     #[serde(rename = "queuedUP")]
     QueuedUP,
@@ -161,6 +169,10 @@ pub enum State {
     /// Torrent is paused and has NOT finished downloading
     #[serde(rename = "pausedDL")]
     PausedDL,
+    /// Torrent is stopped and has NOT finished downloading. Replaces
+    /// `pausedDL` since qBittorrent 5.0.
+    #[serde(rename = "stoppedDL")]
+    StoppedDL,
     /// Queuing is enabled and torrent is queued for download
     #[serde(rename = "queuedDL")]
     QueuedDL,
@@ -184,6 +196,16 @@ pub enum State {
     Unknown,
 }
 
+impl State {
+    /// Whether the torrent is paused, or stopped in qBittorrent 5.0 and later
+    pub fn is_paused(&self) -> bool {
+        matches!(
+            self,
+            Self::PausedDL | Self::PausedUP | Self::StoppedDL | Self::StoppedUP
+        )
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, serde::Deserialize)]
 pub struct TorrentProperty {
     /// Torrent save path
@@ -344,6 +366,21 @@ impl<V: Into<Vec<String>>> From<V> for Hashes {
     }
 }
 
+impl Hashes {
+    /// Split into groups of at most `size` hashes. [`Hashes::All`] is
+    /// returned as is.
+    pub fn chunks(self, size: usize) -> Vec<Hashes> {
+        match self {
+            Hashes::All => vec![Hashes::All],
+            Hashes::Hashes(hashes) => hashes
+                .as_slice()
+                .chunks(size.max(1))
+                .map(|chunk| Hashes::from(chunk.to_vec()))
+                .collect(),
+        }
+    }
+}
+
 impl Display for Hashes {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -353,6 +390,235 @@ impl Display for Hashes {
     }
 }
 
+/// Key used to sort the torrent list returned by `torrents/info`. Every
+/// variant maps to a field of [`Torrent`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SerializeDisplay, DeserializeFromStr)]
+pub enum TorrentSortKey {
+    /// Sort by [`Torrent::added_on`]
+    AddedOn,
+    /// Sort by [`Torrent::amount_left`]
+    AmountLeft,
+    /// Sort by [`Torrent::auto_tmm`]
+    AutoTmm,
+    /// Sort by [`Torrent::availability`]
+    Availability,
+    /// Sort by [`Torrent::category`]
+    Category,
+    /// Sort by [`Torrent::completed`]
+    Completed,
+    /// Sort by [`Torrent::completion_on`]
+    CompletionOn,
+    /// Sort by [`Torrent::content_path`]
+    ContentPath,
+    /// Sort by [`Torrent::dl_limit`]
+    DlLimit,
+    /// Sort by [`Torrent::dlspeed`]
+    Dlspeed,
+    /// Sort by [`Torrent::downloaded`]
+    Downloaded,
+    /// Sort by [`Torrent::downloaded_session`]
+    DownloadedSession,
+    /// Sort by [`Torrent::eta`]
+    Eta,
+    /// Sort by [`Torrent::f_l_piece_prio`]
+    FLPiecePrio,
+    /// Sort by [`Torrent::force_start`]
+    ForceStart,
+    /// Sort by [`Torrent::hash`]
+    Hash,
+    /// Sort by [`Torrent::last_activity`]
+    LastActivity,
+    /// Sort by [`Torrent::magnet_uri`]
+    MagnetUri,
+    /// Sort by [`Torrent::max_ratio`]
+    MaxRatio,
+    /// Sort by [`Torrent::max_seeding_time`]
+    MaxSeedingTime,
+    /// Sort by [`Torrent::name`]
+    Name,
+    /// Sort by [`Torrent::num_complete`]
+    NumComplete,
+    /// Sort by [`Torrent::num_incomplete`]
+    NumIncomplete,
+    /// Sort by [`Torrent::num_leechs`]
+    NumLeechs,
+    /// Sort by [`Torrent::num_seeds`]
+    NumSeeds,
+    /// Sort by [`Torrent::priority`]
+    Priority,
+    /// Sort by [`Torrent::progress`]
+    Progress,
+    /// Sort by [`Torrent::ratio`]
+    Ratio,
+    /// Sort by [`Torrent::ratio_limit`]
+    RatioLimit,
+    /// Sort by [`Torrent::save_path`]
+    SavePath,
+    /// Sort by [`Torrent::seeding_time`]
+    SeedingTime,
+    /// Sort by [`Torrent::seeding_time_limit`]
+    SeedingTimeLimit,
+    /// Sort by [`Torrent::seen_complete`]
+    SeenComplete,
+    /// Sort by [`Torrent::seq_dl`]
+    SeqDl,
+    /// Sort by [`Torrent::size`]
+    Size,
+    /// Sort by [`Torrent::state`]
+    State,
+    /// Sort by [`Torrent::super_seeding`]
+    SuperSeeding,
+    /// Sort by [`Torrent::tags`]
+    Tags,
+    /// Sort by [`Torrent::time_active`]
+    TimeActive,
+    /// Sort by [`Torrent::total_size`]
+    TotalSize,
+    /// Sort by [`Torrent::tracker`]
+    Tracker,
+    /// Sort by [`Torrent::up_limit`]
+    UpLimit,
+    /// Sort by [`Torrent::uploaded`]
+    Uploaded,
+    /// Sort by [`Torrent::uploaded_session`]
+    UploadedSession,
+    /// Sort by [`Torrent::upspeed`]
+    Upspeed,
+    /// Sort by a key not covered by other variants, e.g. a field introduced
+    /// by a newer version of qBittorrent
+    Custom(String),
+}
+
+impl TorrentSortKey {
+    /// JSON key of the field, as expected by the server
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::AddedOn => "added_on",
+            Self::AmountLeft => "amount_left",
+            Self::AutoTmm => "auto_tmm",
+            Self::Availability => "availability",
+            Self::Category => "category",
+            Self::Completed => "completed",
+            Self::CompletionOn => "completion_on",
+            Self::ContentPath => "content_path",
+            Self::DlLimit => "dl_limit",
+            Self::Dlspeed => "dlspeed",
+            Self::Downloaded => "downloaded",
+            Self::DownloadedSession => "downloaded_session",
+            Self::Eta => "eta",
+            Self::FLPiecePrio => "f_l_piece_prio",
+            Self::ForceStart => "force_start",
+            Self::Hash => "hash",
+            Self::LastActivity => "last_activity",
+            Self::MagnetUri => "magnet_uri",
+            Self::MaxRatio => "max_ratio",
+            Self::MaxSeedingTime => "max_seeding_time",
+            Self::Name => "name",
+            Self::NumComplete => "num_complete",
+            Self::NumIncomplete => "num_incomplete",
+            Self::NumLeechs => "num_leechs",
+            Self::NumSeeds => "num_seeds",
+            Self::Priority => "priority",
+            Self::Progress => "progress",
+            Self::Ratio => "ratio",
+            Self::RatioLimit => "ratio_limit",
+            Self::SavePath => "save_path",
+            Self::SeedingTime => "seeding_time",
+            Self::SeedingTimeLimit => "seeding_time_limit",
+            Self::SeenComplete => "seen_complete",
+            Self::SeqDl => "seq_dl",
+            Self::Size => "size",
+            Self::State => "state",
+            Self::SuperSeeding => "super_seeding",
+            Self::Tags => "tags",
+            Self::TimeActive => "time_active",
+            Self::TotalSize => "total_size",
+            Self::Tracker => "tracker",
+            Self::UpLimit => "up_limit",
+            Self::Uploaded => "uploaded",
+            Self::UploadedSession => "uploaded_session",
+            Self::Upspeed => "upspeed",
+            Self::Custom(key) => key,
+        }
+    }
+}
+
+impl Display for TorrentSortKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TorrentSortKey {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "added_on" => Self::AddedOn,
+            "amount_left" => Self::AmountLeft,
+            "auto_tmm" => Self::AutoTmm,
+            "availability" => Self::Availability,
+            "category" => Self::Category,
+            "completed" => Self::Completed,
+            "completion_on" => Self::CompletionOn,
+            "content_path" => Self::ContentPath,
+            "dl_limit" => Self::DlLimit,
+            "dlspeed" => Self::Dlspeed,
+            "downloaded" => Self::Downloaded,
+            "downloaded_session" => Self::DownloadedSession,
+            "eta" => Self::Eta,
+            "f_l_piece_prio" => Self::FLPiecePrio,
+            "force_start" => Self::ForceStart,
+            "hash" => Self::Hash,
+            "last_activity" => Self::LastActivity,
+            "magnet_uri" => Self::MagnetUri,
+            "max_ratio" => Self::MaxRatio,
+            "max_seeding_time" => Self::MaxSeedingTime,
+            "name" => Self::Name,
+            "num_complete" => Self::NumComplete,
+            "num_incomplete" => Self::NumIncomplete,
+            "num_leechs" => Self::NumLeechs,
+            "num_seeds" => Self::NumSeeds,
+            "priority" => Self::Priority,
+            "progress" => Self::Progress,
+            "ratio" => Self::Ratio,
+            "ratio_limit" => Self::RatioLimit,
+            "save_path" => Self::SavePath,
+            "seeding_time" => Self::SeedingTime,
+            "seeding_time_limit" => Self::SeedingTimeLimit,
+            "seen_complete" => Self::SeenComplete,
+            "seq_dl" => Self::SeqDl,
+            "size" => Self::Size,
+            "state" => Self::State,
+            "super_seeding" => Self::SuperSeeding,
+            "tags" => Self::Tags,
+            "time_active" => Self::TimeActive,
+            "total_size" => Self::TotalSize,
+            "tracker" => Self::Tracker,
+            "up_limit" => Self::UpLimit,
+            "uploaded" => Self::Uploaded,
+            "uploaded_session" => Self::UploadedSession,
+            "upspeed" => Self::Upspeed,
+            _ => Self::Custom(s.to_owned()),
+        })
+    }
+}
+
+impl From<&str> for TorrentSortKey {
+    fn from(key: &str) -> Self {
+        match key.parse() {
+            Ok(key) => key,
+            Err(e) => match e {},
+        }
+    }
+}
+
+impl From<String> for TorrentSortKey {
+    fn from(key: String) -> Self {
+        key.as_str().into()
+    }
+}
+
 #[cfg_attr(feature = "builder", derive(typed_builder::TypedBuilder))]
 #[cfg_attr(
     feature = "builder",
@@ -374,7 +640,7 @@ pub struct GetTorrentListArg {
     pub tag: Option<String>,
     /// Sort torrents by given key. They can be sorted using any field of the
     /// response's JSON array (which are documented below) as the sort key.
-    pub sort: Option<String>,
+    pub sort: Option<TorrentSortKey>,
     /// Enable reverse sorting. Defaults to `false`
     pub reverse: Option<bool>,
     /// Limit the number of torrents returned
@@ -532,3 +798,31 @@ impl HashesArg {
         }
     }
 }
+
+#[test]
+fn test_sort_key() {
+    assert_eq!(TorrentSortKey::AddedOn.to_string(), "added_on");
+    assert_eq!(TorrentSortKey::FLPiecePrio.to_string(), "f_l_piece_prio");
+    assert_eq!(TorrentSortKey::from("ratio"), TorrentSortKey::Ratio);
+    assert_eq!(
+        TorrentSortKey::from("popularity"),
+        TorrentSortKey::Custom("popularity".to_owned())
+    );
+
+    let arg = GetTorrentListArg {
+        sort: Some(TorrentSortKey::Dlspeed),
+        ..Default::default()
+    };
+    assert_eq!(serde_json::to_value(&arg).unwrap()["sort"], "dlspeed");
+}
+
+#[test]
+fn test_hashes_chunks() {
+    let hashes = Hashes::from(vec!["a".to_owned(), "b".to_owned(), "c".to_owned()]);
+    let chunks = hashes.chunks(2);
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks[0].to_string(), "a|b");
+    assert_eq!(chunks[1].to_string(), "c");
+
+    assert_eq!(Hashes::All.chunks(2), vec![Hashes::All]);
+}
@@ -0,0 +1,410 @@
+//! Several named [`Qbit`] clients used together.
+//!
+//! [`QbitPool`] sends read-only calls to every instance concurrently and tags
+//! each result with the instance it came from. Operations taking [`Hashes`]
+//! are routed to the instances that own the torrents.
+//!
+//! ```rust,ignore
+//! use qbit_rs::{pool::QbitPool, Qbit};
+//!
+//! let pool = QbitPool::from_iter([("seedbox-1", api1), ("seedbox-2", api2)]);
+//! for list in pool.get_torrent_list(Default::default()).await {
+//!     println!("{}: {:?}", list.instance, list.value.map(|t| t.len()));
+//! }
+//! pool.pause_torrents(vec![hash]).await?;
+//! ```
+
+use std::{
+    collections::HashSet,
+    future::Future,
+    sync::{Mutex, PoisonError},
+};
+
+use futures_util::future::join_all;
+use tracing::debug;
+
+use crate::{
+    batch::{BatchFailure, BatchOptions},
+    model::{GetTorrentListArg, Hashes, SyncData, Torrent, TransferInfo},
+    Error, Qbit, Result,
+};
+
+/// A value returned by one instance of a [`QbitPool`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tagged<T> {
+    /// Name of the instance
+    pub instance: String,
+    pub value: T,
+}
+
+struct Instance {
+    name: String,
+    api: Qbit,
+    /// Response ID of the last successful [`QbitPool::sync`]
+    rid: Mutex<Option<i64>>,
+}
+
+/// Named [`Qbit`] clients, see the [module documentation](self)
+#[derive(Default)]
+pub struct QbitPool {
+    instances: Vec<Instance>,
+}
+
+impl QbitPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `api` as `name`, returning the client previously registered with
+    /// that name, if any.
+    pub fn insert(&mut self, name: impl Into<String>, api: Qbit) -> Option<Qbit> {
+        let name = name.into();
+        let previous = self.remove(&name);
+        self.instances.push(Instance {
+            name,
+            api,
+            rid: Mutex::new(None),
+        });
+        previous
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Qbit> {
+        let index = self.instances.iter().position(|i| i.name == name)?;
+        Some(self.instances.remove(index).api)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Qbit> {
+        self.instances
+            .iter()
+            .find(|i| i.name == name)
+            .map(|i| &i.api)
+    }
+
+    /// Names of the instances, in insertion order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.instances.iter().map(|i| i.name.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    /// Run `op` on every instance concurrently
+    pub async fn fan_out<'a, T, F, Fut>(&'a self, op: F) -> Vec<Tagged<Result<T>>>
+    where
+        F: Fn(&'a Qbit) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        join_all(self.instances.iter().map(|instance| {
+            let fut = op(&instance.api);
+            async move {
+                Tagged {
+                    instance: instance.name.clone(),
+                    value: fut.await,
+                }
+            }
+        }))
+        .await
+    }
+
+    pub async fn get_torrent_list(
+        &self,
+        arg: GetTorrentListArg,
+    ) -> Vec<Tagged<Result<Vec<Torrent>>>> {
+        self.fan_out(|api| api.get_torrent_list(arg.clone())).await
+    }
+
+    pub async fn get_transfer_info(&self) -> Vec<Tagged<Result<TransferInfo>>> {
+        self.fan_out(Qbit::get_transfer_info).await
+    }
+
+    /// Sync every instance. Each instance continues from the response ID of
+    /// its last successful sync, so only the first call returns full updates.
+    pub async fn sync(&self) -> Vec<Tagged<Result<SyncData>>> {
+        join_all(self.instances.iter().map(|instance| async move {
+            let rid = *instance.rid.lock().unwrap_or_else(PoisonError::into_inner);
+            let value = instance.api.sync(rid).await;
+            if let Ok(data) = &value {
+                *instance.rid.lock().unwrap_or_else(PoisonError::into_inner) = Some(data.rid);
+            }
+            Tagged {
+                instance: instance.name.clone(),
+                value,
+            }
+        }))
+        .await
+    }
+
+    /// Forget the response IDs of previous syncs, so that the next
+    /// [`sync`](Self::sync) returns full updates
+    pub fn reset_sync(&self) {
+        for instance in &self.instances {
+            *instance.rid.lock().unwrap_or_else(PoisonError::into_inner) = None;
+        }
+    }
+
+    /// Run `op` on each instance owning some of `hashes`, with the hashes it
+    /// owns in chunks of [`BatchOptions::chunk_size`]. [`Hashes::All`] is sent
+    /// to every instance. A torrent present on several instances is sent to
+    /// each of them, and hashes not found on any instance are ignored.
+    ///
+    /// Chunks where `op` fails are collected into [`Error::BatchFailed`], and
+    /// so are instances whose torrents cannot be listed while some hashes were
+    /// not found elsewhere.
+    pub async fn routed<'a, F, Fut>(
+        &'a self,
+        hashes: impl Into<Hashes> + Send + Sync,
+        op: F,
+    ) -> Result<()>
+    where
+        F: Fn(&'a Qbit, Hashes) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<()>> + Send,
+    {
+        let (groups, mut failures) = match hashes.into() {
+            Hashes::All => (
+                self.instances
+                    .iter()
+                    .map(|i| (&i.api, Hashes::All))
+                    .collect(),
+                vec![],
+            ),
+            Hashes::Hashes(hashes) => self.locate(hashes.into_inner()).await,
+        };
+        let chunk_size = BatchOptions::default().chunk_size;
+        let calls = groups
+            .into_iter()
+            .flat_map(|(api, hashes)| {
+                hashes
+                    .chunks(chunk_size)
+                    .into_iter()
+                    .map(move |chunk| (api, chunk))
+            })
+            .collect::<Vec<_>>();
+        let total = calls.len() + failures.len();
+
+        let results = join_all(calls.into_iter().map(|(api, hashes)| {
+            let fut = op(api, hashes.clone());
+            async move { fut.await.err().map(|error| BatchFailure { hashes, error }) }
+        }))
+        .await;
+        failures.extend(results.into_iter().flatten());
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::BatchFailed { total, failures })
+        }
+    }
+
+    /// Group `hashes` by the instance owning them, looking them up in chunks
+    /// of [`BatchOptions::chunk_size`] to keep the query short
+    async fn locate(&self, hashes: Vec<String>) -> (Vec<(&Qbit, Hashes)>, Vec<BatchFailure>) {
+        let chunks = Hashes::from(hashes.clone()).chunks(BatchOptions::default().chunk_size);
+        let lists = self
+            .fan_out(|api| {
+                let chunks = &chunks;
+                async move {
+                    let mut torrents = vec![];
+                    for chunk in chunks {
+                        let arg = GetTorrentListArg {
+                            hashes: Some(chunk.to_string()),
+                            ..Default::default()
+                        };
+                        torrents.extend(api.get_torrent_list(arg).await?);
+                    }
+                    Ok(torrents)
+                }
+            })
+            .await;
+
+        let requested = hashes
+            .iter()
+            .map(|h| h.to_lowercase())
+            .collect::<HashSet<_>>();
+        let mut resolved = HashSet::new();
+        let mut groups = vec![];
+        let mut errors = vec![];
+        for (instance, list) in self.instances.iter().zip(lists) {
+            match list.value {
+                Ok(torrents) => {
+                    let mut owned = torrents
+                        .into_iter()
+                        .filter_map(|t| t.hash)
+                        .map(|h| h.to_lowercase())
+                        .filter(|h| requested.contains(h))
+                        .collect::<Vec<_>>();
+                    owned.sort_unstable();
+                    owned.dedup();
+                    resolved.extend(owned.iter().cloned());
+                    if !owned.is_empty() {
+                        groups.push((&instance.api, Hashes::from(owned)));
+                    }
+                }
+                Err(error) => errors.push(error),
+            }
+        }
+
+        let unresolved = requested
+            .into_iter()
+            .filter(|h| !resolved.contains(h))
+            .collect::<Vec<_>>();
+        if unresolved.is_empty() {
+            return (groups, vec![]);
+        }
+        debug!(count = unresolved.len(), "Hashes not found on any instance");
+        // Torrents that were not found may belong to instances that could
+        // not be listed
+        let failures = errors
+            .into_iter()
+            .map(|error| BatchFailure {
+                hashes: Hashes::from(unresolved.clone()),
+                error,
+            })
+            .collect();
+
+        (groups, failures)
+    }
+
+    pub async fn pause_torrents(&self, hashes: impl Into<Hashes> + Send + Sync) -> Result<()> {
+        self.routed(hashes, Qbit::pause_torrents).await
+    }
+
+    pub async fn resume_torrents(&self, hashes: impl Into<Hashes> + Send + Sync) -> Result<()> {
+        self.routed(hashes, Qbit::resume_torrents).await
+    }
+
+    pub async fn delete_torrents(
+        &self,
+        hashes: impl Into<Hashes> + Send + Sync,
+        delete_files: impl Into<Option<bool>> + Send + Sync,
+    ) -> Result<()> {
+        let delete_files = delete_files.into();
+        self.routed(hashes, |api, hashes| {
+            api.delete_torrents(hashes, delete_files)
+        })
+        .await
+    }
+
+    pub async fn recheck_torrents(&self, hashes: impl Into<Hashes> + Send + Sync) -> Result<()> {
+        self.routed(hashes, Qbit::recheck_torrents).await
+    }
+
+    pub async fn reannounce_torrents(&self, hashes: impl Into<Hashes> + Send + Sync) -> Result<()> {
+        self.routed(hashes, Qbit::reannounce_torrents).await
+    }
+}
+
+impl<N: Into<String>> FromIterator<(N, Qbit)> for QbitPool {
+    fn from_iter<I: IntoIterator<Item = (N, Qbit)>>(iter: I) -> Self {
+        let mut pool = Self::new();
+        for (name, api) in iter {
+            pool.insert(name, api);
+        }
+        pool
+    }
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_pool() {
+    use crate::mock::{self, serve, MockResponse, Requests};
+
+    async fn instance(owned: &'static str) -> (Qbit, Requests) {
+        let (url, requests) = serve(move |req| {
+            if req.path.starts_with("/api/v2/torrents/info") {
+                if req.path.to_lowercase().contains(owned) {
+                    MockResponse::ok(format!(r#"[{{"hash":"{owned}"}}]"#))
+                } else {
+                    MockResponse::ok("[]")
+                }
+            } else {
+                MockResponse::ok("")
+            }
+        })
+        .await;
+        (mock::client(url), requests)
+    }
+
+    let (a, a_requests) = instance("aaa").await;
+    let (b, b_requests) = instance("bbb").await;
+    let pool = QbitPool::from_iter([("a", a), ("b", b)]);
+    assert_eq!(pool.names().collect::<Vec<_>>(), ["a", "b"]);
+
+    let lists = pool.get_torrent_list(Default::default()).await;
+    assert_eq!(lists[1].instance, "b");
+    assert!(lists.iter().all(|l| l.value.is_ok()));
+
+    pool.pause_torrents(vec!["AAA".to_owned(), "bbb".to_owned(), "ccc".to_owned()])
+        .await
+        .unwrap();
+    let paused = |requests: &Requests| requests.posted("/api/v2/torrents/pause");
+    assert_eq!(paused(&a_requests), ["hashes=aaa"]);
+    assert_eq!(paused(&b_requests), ["hashes=bbb"]);
+
+    // Lookups are chunked
+    let hashes = (0..250).map(|i| format!("{i:040x}")).collect::<Vec<_>>();
+    pool.pause_torrents(hashes).await.unwrap();
+    let listed = a_requests
+        .paths()
+        .iter()
+        .filter(|p| p.starts_with("/api/v2/torrents/info?hashes="))
+        .count();
+    assert_eq!(listed, 1 + 2);
+
+    // An instance that cannot be listed only fails hashes not found elsewhere
+    let (url, _) = serve(|_| MockResponse::status(500)).await;
+    let mut pool = pool;
+    pool.insert("c", mock::client(url));
+    pool.pause_torrents(vec!["aaa".to_owned(), "bbb".to_owned()])
+        .await
+        .unwrap();
+    let res = pool
+        .pause_torrents(vec!["aaa".to_owned(), "ccc".to_owned()])
+        .await;
+    match res {
+        Err(Error::BatchFailed { total, failures }) => {
+            assert_eq!(total, 2);
+            assert_eq!(failures.len(), 1);
+            assert_eq!(failures[0].hashes.to_string(), "ccc");
+            assert!(matches!(
+                failures[0].error,
+                Error::UnknownHttpCode { status, .. } if status == 500
+            ));
+        }
+        res => panic!("Unexpected result: {res:?}"),
+    }
+    assert_eq!(
+        paused(&a_requests),
+        ["hashes=aaa", "hashes=aaa", "hashes=aaa"]
+    );
+
+    // A mirror owns every torrent it is asked about, so hashes are sent to
+    // both the mirror and their other owner, in chunks
+    let (url, m_requests) = serve(|req| match req.path.split_once("?hashes=") {
+        Some((_, hashes)) => MockResponse::ok(format!(
+            "[{}]",
+            hashes
+                .split("%7C")
+                .map(|h| format!(r#"{{"hash":"{h}"}}"#))
+                .collect::<Vec<_>>()
+                .join(",")
+        )),
+        None => MockResponse::ok(""),
+    })
+    .await;
+    let (a, a_requests) = instance("aaa").await;
+    let pool = QbitPool::from_iter([("a", a), ("m", mock::client(url))]);
+    pool.pause_torrents(vec!["aaa".to_owned(), "bbb".to_owned()])
+        .await
+        .unwrap();
+    assert_eq!(paused(&a_requests), ["hashes=aaa"]);
+    assert_eq!(paused(&m_requests), ["hashes=aaa%7Cbbb"]);
+
+    let hashes = (0..250).map(|i| format!("{i:040x}")).collect::<Vec<_>>();
+    pool.pause_torrents(hashes).await.unwrap();
+    assert_eq!(paused(&m_requests).len(), 1 + 2);
+}
@@ -0,0 +1,162 @@
+//! Loading [`Preferences`] from TOML or YAML files and saving them back.
+//!
+//! Files hold a subset of the preferences, using the field names of the API.
+//! Loaded preferences are [validated](Preferences::validate), so that a typo
+//! in a port or an enum-like value is caught before it reaches the server.
+//!
+//! ```rust,ignore
+//! use qbit_rs::model::Preferences;
+//!
+//! let desired = Preferences::load("qbittorrent.toml")?;
+//! api.apply_preferences(&desired).await?;
+//! api.dump_preferences("current.yaml").await?;
+//! ```
+
+use std::{fs, io, path::Path};
+
+use crate::{model::Preferences, Error, Qbit, Result};
+
+/// Format of a preferences file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    #[cfg(feature = "toml")]
+    #[cfg_attr(feature = "docs", doc(cfg(feature = "toml")))]
+    Toml,
+    #[cfg(feature = "yaml")]
+    #[cfg_attr(feature = "docs", doc(cfg(feature = "yaml")))]
+    Yaml,
+}
+
+impl Format {
+    /// Format matching the extension of `path`: `.toml`, `.yaml` or `.yml`
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            #[cfg(feature = "toml")]
+            "toml" => Some(Self::Toml),
+            #[cfg(feature = "yaml")]
+            "yaml" | "yml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+
+    fn of(path: &Path) -> Result<Self> {
+        Self::from_path(path)
+            .ok_or_else(|| FileError::UnknownFormat(path.display().to_string()).into())
+    }
+}
+
+/// Errors reading, parsing or writing a preferences file
+#[derive(Debug, thiserror::Error)]
+pub enum FileError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+
+    #[error("Unknown format of {0}, expected a .toml, .yaml or .yml file")]
+    UnknownFormat(String),
+
+    #[cfg(feature = "toml")]
+    #[error("Invalid TOML: {0}")]
+    TomlDe(#[from] toml::de::Error),
+
+    #[cfg(feature = "toml")]
+    #[error("Failed to serialize TOML: {0}")]
+    TomlSer(#[from] toml::ser::Error),
+
+    #[cfg(feature = "yaml")]
+    #[error("YAML error: {0}")]
+    Yaml(#[from] serde_norway::Error),
+}
+
+impl Preferences {
+    /// Parse and validate preferences in `format`
+    pub fn from_str_as(s: &str, format: Format) -> Result<Self> {
+        let preferences: Self = match format {
+            #[cfg(feature = "toml")]
+            Format::Toml => toml::from_str(s).map_err(FileError::from)?,
+            #[cfg(feature = "yaml")]
+            Format::Yaml => serde_norway::from_str(s).map_err(FileError::from)?,
+        };
+        preferences.validate().map_err(Error::InvalidPreferences)?;
+        Ok(preferences)
+    }
+
+    /// Load and validate preferences from `path`, in the format given by its
+    /// extension
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let format = Format::of(path)?;
+        let s = fs::read_to_string(path).map_err(FileError::from)?;
+        Self::from_str_as(&s, format)
+    }
+
+    /// Serialize the fields that are set in `format`
+    pub fn to_string_as(&self, format: Format) -> Result<String> {
+        Ok(match format {
+            #[cfg(feature = "toml")]
+            Format::Toml => toml::to_string_pretty(self).map_err(FileError::from)?,
+            #[cfg(feature = "yaml")]
+            Format::Yaml => serde_norway::to_string(self).map_err(FileError::from)?,
+        })
+    }
+
+    /// Save the fields that are set to `path`, in the format given by its
+    /// extension
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let s = self.to_string_as(Format::of(path)?)?;
+        fs::write(path, s).map_err(|e| FileError::from(e).into())
+    }
+}
+
+impl Qbit {
+    /// Save the current preferences of the server to `path`, in the format
+    /// given by its extension, and return them.
+    pub async fn dump_preferences(&self, path: impl AsRef<Path> + Send) -> Result<Preferences> {
+        let preferences = self.get_preferences().await?;
+        preferences.save(path)?;
+        Ok(preferences)
+    }
+}
+
+#[cfg(all(test, feature = "toml", feature = "yaml"))]
+#[tokio::test]
+async fn test_preferences_file() {
+    use crate::{
+        mock::{self, serve, MockResponse},
+        model::Encryption,
+    };
+
+    let preferences = Preferences::from_str_as(
+        "listen_port = 51413\nencryption = 1\n\n[scan_dirs]\n\"/watch\" = 0\n",
+        Format::Toml,
+    )
+    .unwrap();
+    assert_eq!(preferences.listen_port, Some(51413));
+    assert_eq!(preferences.encryption, Some(Encryption::ForceOn.into()));
+    assert_eq!(preferences.dht, None);
+
+    let yaml = preferences.to_string_as(Format::Yaml).unwrap();
+    assert_eq!(
+        Preferences::from_str_as(&yaml, Format::Yaml).unwrap(),
+        preferences
+    );
+    assert!(matches!(
+        Preferences::from_str_as("encryption: 5\nweb_ui_port: 0\n", Format::Yaml),
+        Err(Error::InvalidPreferences(errors)) if errors.len() == 2
+    ));
+    assert!(matches!(
+        Preferences::load("preferences.json"),
+        Err(Error::PreferencesFile(FileError::UnknownFormat(_)))
+    ));
+
+    let (url, _) = serve(|_| MockResponse::ok(r#"{"listen_port":6881,"dht":true}"#)).await;
+    let api = mock::client(url);
+    let path = std::env::temp_dir().join(format!("qbit-preferences-{}.toml", std::process::id()));
+    let dumped = api.dump_preferences(&path).await.unwrap();
+    assert_eq!(Preferences::load(&path).unwrap(), dumped);
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "listen_port = 6881\ndht = true\n"
+    );
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,515 @@
+//! Client-side queries over torrent lists.
+//!
+//! `torrents/info` only filters by a single state, category, tag and a list of
+//! hashes. [`TorrentQuery`] composes arbitrary predicates over [`Torrent`]
+//! fields, pushes what the server understands into a [`GetTorrentListArg`]
+//! and evaluates the rest locally. See [`Qbit::query_torrents`].
+//!
+//! ```rust,ignore
+//! use qbit_rs::{model::TorrentSortKey, query::TorrentQuery};
+//!
+//! let query = TorrentQuery::field(TorrentSortKey::SeedingTime)
+//!     .gt(30 * 24 * 3600)
+//!     .and(TorrentQuery::field(TorrentSortKey::Ratio).gt(2.0))
+//!     .and(TorrentQuery::field(TorrentSortKey::Tracker).contains("example.org"))
+//!     .and(!TorrentQuery::tag("keep"));
+//! let torrents = api.query_torrents(&query).await?;
+//! ```
+//!
+//! [`Qbit::query_torrents`]: crate::Qbit::query_torrents
+
+use std::{borrow::Cow, cmp::Ordering, ops::Not};
+
+#[cfg(feature = "regex")]
+use regex::Regex;
+
+use crate::model::{GetTorrentListArg, State, Torrent, TorrentFilter, TorrentSortKey};
+
+/// A predicate over [`Torrent`].
+///
+/// Comparisons against a field that is missing from the torrent, or that has
+/// a different type than the compared value, never match.
+#[derive(Debug, Clone)]
+pub enum TorrentQuery {
+    /// Matches every torrent
+    Any,
+    /// Torrent matches the state filter
+    Filter(TorrentFilter),
+    /// Torrent is in the category. Empty string means "without category"
+    Category(String),
+    /// Torrent has the tag. Empty string means "without tag"
+    Tag(String),
+    /// Torrent hash is one of the given hashes
+    Hashes(Vec<String>),
+    /// Torrent is in the exact state
+    State(State),
+    /// Field compared against a value
+    Compare {
+        field: TorrentSortKey,
+        op: CmpOp,
+        value: QueryValue,
+    },
+    /// String field contains the substring
+    Contains {
+        field: TorrentSortKey,
+        needle: String,
+    },
+    /// String field matches the regex
+    #[cfg(feature = "regex")]
+    #[cfg_attr(feature = "docs", doc(cfg(feature = "regex")))]
+    Matches { field: TorrentSortKey, regex: Regex },
+    /// All of the queries match
+    And(Vec<TorrentQuery>),
+    /// Any of the queries match
+    Or(Vec<TorrentQuery>),
+    /// The query does not match
+    Not(Box<TorrentQuery>),
+}
+
+/// Comparison operator used by [`TorrentQuery::Compare`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+/// Value a torrent field is compared against
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryValue {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+}
+
+impl From<i64> for QueryValue {
+    fn from(v: i64) -> Self {
+        Self::Int(v)
+    }
+}
+
+impl From<i32> for QueryValue {
+    fn from(v: i32) -> Self {
+        Self::Int(v.into())
+    }
+}
+
+impl From<f64> for QueryValue {
+    fn from(v: f64) -> Self {
+        Self::Float(v)
+    }
+}
+
+impl From<bool> for QueryValue {
+    fn from(v: bool) -> Self {
+        Self::Bool(v)
+    }
+}
+
+impl From<&str> for QueryValue {
+    fn from(v: &str) -> Self {
+        Self::Str(v.to_owned())
+    }
+}
+
+impl From<String> for QueryValue {
+    fn from(v: String) -> Self {
+        Self::Str(v)
+    }
+}
+
+/// Builder for predicates over a single field, created by
+/// [`TorrentQuery::field`]
+#[derive(Debug, Clone)]
+pub struct FieldQuery(TorrentSortKey);
+
+impl FieldQuery {
+    fn compare(self, op: CmpOp, value: impl Into<QueryValue>) -> TorrentQuery {
+        TorrentQuery::Compare {
+            field: self.0,
+            op,
+            value: value.into(),
+        }
+    }
+
+    pub fn eq(self, value: impl Into<QueryValue>) -> TorrentQuery {
+        self.compare(CmpOp::Eq, value)
+    }
+
+    pub fn ne(self, value: impl Into<QueryValue>) -> TorrentQuery {
+        self.compare(CmpOp::Ne, value)
+    }
+
+    pub fn gt(self, value: impl Into<QueryValue>) -> TorrentQuery {
+        self.compare(CmpOp::Gt, value)
+    }
+
+    pub fn ge(self, value: impl Into<QueryValue>) -> TorrentQuery {
+        self.compare(CmpOp::Ge, value)
+    }
+
+    pub fn lt(self, value: impl Into<QueryValue>) -> TorrentQuery {
+        self.compare(CmpOp::Lt, value)
+    }
+
+    pub fn le(self, value: impl Into<QueryValue>) -> TorrentQuery {
+        self.compare(CmpOp::Le, value)
+    }
+
+    pub fn contains(self, needle: impl Into<String>) -> TorrentQuery {
+        TorrentQuery::Contains {
+            field: self.0,
+            needle: needle.into(),
+        }
+    }
+
+    #[cfg(feature = "regex")]
+    #[cfg_attr(feature = "docs", doc(cfg(feature = "regex")))]
+    pub fn matches(self, regex: Regex) -> TorrentQuery {
+        TorrentQuery::Matches {
+            field: self.0,
+            regex,
+        }
+    }
+}
+
+impl TorrentQuery {
+    pub fn any() -> Self {
+        Self::Any
+    }
+
+    pub fn filter(filter: TorrentFilter) -> Self {
+        Self::Filter(filter)
+    }
+
+    pub fn category(category: impl Into<String>) -> Self {
+        Self::Category(category.into())
+    }
+
+    pub fn tag(tag: impl Into<String>) -> Self {
+        Self::Tag(tag.into())
+    }
+
+    pub fn hashes<I: IntoIterator<Item = S>, S: Into<String>>(hashes: I) -> Self {
+        Self::Hashes(hashes.into_iter().map(Into::into).collect())
+    }
+
+    pub fn state(state: State) -> Self {
+        Self::State(state)
+    }
+
+    pub fn field(field: TorrentSortKey) -> FieldQuery {
+        FieldQuery(field)
+    }
+
+    /// Torrent name matches the regex
+    #[cfg(feature = "regex")]
+    #[cfg_attr(feature = "docs", doc(cfg(feature = "regex")))]
+    pub fn name_matches(regex: Regex) -> Self {
+        Self::field(TorrentSortKey::Name).matches(regex)
+    }
+
+    /// Current tracker matches the regex
+    #[cfg(feature = "regex")]
+    #[cfg_attr(feature = "docs", doc(cfg(feature = "regex")))]
+    pub fn tracker_matches(regex: Regex) -> Self {
+        Self::field(TorrentSortKey::Tracker).matches(regex)
+    }
+
+    pub fn and(self, other: TorrentQuery) -> Self {
+        match self {
+            Self::And(mut queries) => {
+                queries.push(other);
+                Self::And(queries)
+            }
+            this => Self::And(vec![this, other]),
+        }
+    }
+
+    pub fn or(self, other: TorrentQuery) -> Self {
+        match self {
+            Self::Or(mut queries) => {
+                queries.push(other);
+                Self::Or(queries)
+            }
+            this => Self::Or(vec![this, other]),
+        }
+    }
+
+    /// Split the query into the part that `torrents/info` can evaluate and the
+    /// remaining part that has to be evaluated locally.
+    ///
+    /// Only top-level conjuncts are pushed to the server, at most one of each
+    /// kind; everything else stays in the returned residual query.
+    pub fn split(&self) -> (GetTorrentListArg, TorrentQuery) {
+        let conjuncts = match self {
+            Self::And(queries) => queries.as_slice(),
+            query => std::slice::from_ref(query),
+        };
+
+        let mut arg = GetTorrentListArg::default();
+        let mut residual = vec![];
+
+        for query in conjuncts {
+            match query {
+                Self::Any => {}
+                Self::Filter(filter) if arg.filter.is_none() => arg.filter = Some(filter.clone()),
+                Self::Category(category) if arg.category.is_none() => {
+                    arg.category = Some(category.clone())
+                }
+                Self::Tag(tag) if arg.tag.is_none() => arg.tag = Some(tag.clone()),
+                // An empty `hashes` parameter is ignored by the server, while the
+                // query matches nothing
+                Self::Hashes(hashes) if arg.hashes.is_none() && !hashes.is_empty() => {
+                    arg.hashes = Some(hashes.join("|"))
+                }
+                query => residual.push(query.clone()),
+            }
+        }
+
+        let residual = match residual.len() {
+            0 => Self::Any,
+            1 => residual.pop().unwrap(),
+            _ => Self::And(residual),
+        };
+
+        (arg, residual)
+    }
+
+    /// Evaluate the query against a torrent locally
+    pub fn matches(&self, torrent: &Torrent) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Filter(filter) => filter_matches(filter, torrent),
+            Self::Category(category) => torrent.category.as_deref() == Some(category),
+            Self::Tag(tag) => {
+                let mut tags = torrent
+                    .tags
+                    .as_deref()
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|t| !t.is_empty());
+                if tag.is_empty() {
+                    tags.next().is_none()
+                } else {
+                    tags.any(|t| t == tag)
+                }
+            }
+            Self::Hashes(hashes) => torrent
+                .hash
+                .as_deref()
+                .is_some_and(|h| hashes.iter().any(|x| x.eq_ignore_ascii_case(h))),
+            Self::State(state) => torrent.state.as_ref() == Some(state),
+            Self::Compare { field, op, value } => field_value(torrent, field)
+                .and_then(|v| v.compare(value))
+                .is_some_and(|ord| match op {
+                    CmpOp::Eq => ord.is_eq(),
+                    CmpOp::Ne => ord.is_ne(),
+                    CmpOp::Gt => ord.is_gt(),
+                    CmpOp::Ge => ord.is_ge(),
+                    CmpOp::Lt => ord.is_lt(),
+                    CmpOp::Le => ord.is_le(),
+                }),
+            Self::Contains { field, needle } => match field_value(torrent, field) {
+                Some(Value::Str(s)) => s.contains(needle.as_str()),
+                _ => false,
+            },
+            #[cfg(feature = "regex")]
+            Self::Matches { field, regex } => match field_value(torrent, field) {
+                Some(Value::Str(s)) => regex.is_match(&s),
+                _ => false,
+            },
+            Self::And(queries) => queries.iter().all(|q| q.matches(torrent)),
+            Self::Or(queries) => queries.iter().any(|q| q.matches(torrent)),
+            Self::Not(query) => !query.matches(torrent),
+        }
+    }
+}
+
+impl Not for TorrentQuery {
+    type Output = Self;
+
+    fn not(self) -> Self::Output {
+        match self {
+            Self::Not(query) => *query,
+            query => Self::Not(Box::new(query)),
+        }
+    }
+}
+
+/// Local approximation of the server-side state filters
+fn filter_matches(filter: &TorrentFilter, torrent: &Torrent) -> bool {
+    use State::*;
+
+    let state = torrent.state.as_ref();
+    let active = torrent.dlspeed.unwrap_or(0) > 0 || torrent.upspeed.unwrap_or(0) > 0;
+    let paused = state.is_some_and(State::is_paused);
+
+    match filter {
+        TorrentFilter::All => true,
+        TorrentFilter::Downloading => matches!(
+            state,
+            Some(
+                Downloading
+                    | MetaDL
+                    | PausedDL
+                    | StoppedDL
+                    | QueuedDL
+                    | StalledDL
+                    | CheckingDL
+                    | ForcedDL
+                    | Allocating
+            )
+        ),
+        TorrentFilter::Completed => matches!(
+            state,
+            Some(Uploading | PausedUP | StoppedUP | QueuedUP | StalledUP | CheckingUP | ForcedUP)
+        ),
+        TorrentFilter::Paused => paused,
+        TorrentFilter::Active => active,
+        TorrentFilter::Inactive => !active,
+        TorrentFilter::Resumed => !paused,
+        TorrentFilter::Stalled => matches!(state, Some(StalledUP | StalledDL)),
+        TorrentFilter::StalledUploading => matches!(state, Some(StalledUP)),
+        TorrentFilter::StalledDownloading => matches!(state, Some(StalledDL)),
+        TorrentFilter::Errored => matches!(state, Some(Error | MissingFiles)),
+    }
+}
+
+enum Value<'a> {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(Cow<'a, str>),
+}
+
+impl Value<'_> {
+    fn compare(&self, other: &QueryValue) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Int(a), QueryValue::Int(b)) => Some(a.cmp(b)),
+            (Value::Int(a), QueryValue::Float(b)) => (*a as f64).partial_cmp(b),
+            (Value::Float(a), QueryValue::Int(b)) => a.partial_cmp(&(*b as f64)),
+            (Value::Float(a), QueryValue::Float(b)) => a.partial_cmp(b),
+            (Value::Bool(a), QueryValue::Bool(b)) => Some(a.cmp(b)),
+            (Value::Str(a), QueryValue::Str(b)) => Some(a.as_ref().cmp(b.as_str())),
+            _ => None,
+        }
+    }
+}
+
+fn state_str(state: &State) -> Option<Cow<'static, str>> {
+    match serde_json::to_value(state) {
+        Ok(serde_json::Value::String(s)) => Some(Cow::Owned(s)),
+        _ => None,
+    }
+}
+
+fn field_value<'a>(t: &'a Torrent, field: &TorrentSortKey) -> Option<Value<'a>> {
+    use TorrentSortKey as K;
+
+    match field {
+        K::AddedOn => t.added_on.map(Value::Int),
+        K::AmountLeft => t.amount_left.map(Value::Int),
+        K::AutoTmm => t.auto_tmm.map(Value::Bool),
+        K::Availability => t.availability.map(Value::Float),
+        K::Category => t.category.as_deref().map(|v| Value::Str(Cow::Borrowed(v))),
+        K::Completed => t.completed.map(Value::Int),
+        K::CompletionOn => t.completion_on.map(Value::Int),
+        K::ContentPath => t
+            .content_path
+            .as_deref()
+            .map(|v| Value::Str(Cow::Borrowed(v))),
+        K::DlLimit => t.dl_limit.map(Value::Int),
+        K::Dlspeed => t.dlspeed.map(Value::Int),
+        K::Downloaded => t.downloaded.map(Value::Int),
+        K::DownloadedSession => t.downloaded_session.map(Value::Int),
+        K::Eta => t.eta.map(Value::Int),
+        K::FLPiecePrio => t.f_l_piece_prio.map(Value::Bool),
+        K::ForceStart => t.force_start.map(Value::Bool),
+        K::Hash => t.hash.as_deref().map(|v| Value::Str(Cow::Borrowed(v))),
+        K::LastActivity => t.last_activity.map(Value::Int),
+        K::MagnetUri => t
+            .magnet_uri
+            .as_deref()
+            .map(|v| Value::Str(Cow::Borrowed(v))),
+        K::MaxRatio => t.max_ratio.map(Value::Float),
+        K::MaxSeedingTime => t.max_seeding_time.map(Value::Int),
+        K::Name => t.name.as_deref().map(|v| Value::Str(Cow::Borrowed(v))),
+        K::NumComplete => t.num_complete.map(Value::Int),
+        K::NumIncomplete => t.num_incomplete.map(Value::Int),
+        K::NumLeechs => t.num_leechs.map(Value::Int),
+        K::NumSeeds => t.num_seeds.map(Value::Int),
+        K::Priority => t.priority.map(Value::Int),
+        K::Progress => t.progress.map(Value::Float),
+        K::Ratio => t.ratio.map(Value::Float),
+        K::RatioLimit => t.ratio_limit.map(Value::Float),
+        K::SavePath => t.save_path.as_deref().map(|v| Value::Str(Cow::Borrowed(v))),
+        K::SeedingTime => t.seeding_time.map(Value::Int),
+        K::SeedingTimeLimit => t.seeding_time_limit.map(Value::Int),
+        K::SeenComplete => t.seen_complete.map(Value::Int),
+        K::SeqDl => t.seq_dl.map(Value::Bool),
+        K::Size => t.size.map(Value::Int),
+        K::State => t.state.as_ref().and_then(state_str).map(Value::Str),
+        K::SuperSeeding => t.super_seeding.map(Value::Bool),
+        K::Tags => t.tags.as_deref().map(|v| Value::Str(Cow::Borrowed(v))),
+        K::TimeActive => t.time_active.map(Value::Int),
+        K::TotalSize => t.total_size.map(Value::Int),
+        K::Tracker => t.tracker.as_deref().map(|v| Value::Str(Cow::Borrowed(v))),
+        K::UpLimit => t.up_limit.map(Value::Int),
+        K::Uploaded => t.uploaded.map(Value::Int),
+        K::UploadedSession => t.uploaded_session.map(Value::Int),
+        K::Upspeed => t.upspeed.map(Value::Int),
+        K::Custom(_) => None,
+    }
+}
+
+#[test]
+fn test_query() {
+    let torrent: Torrent = serde_json::from_value(serde_json::json!({
+        "hash": "ABCDEF",
+        "name": "Ubuntu 22.04 Desktop",
+        "ratio": 2.5,
+        "seeding_time": 40 * 24 * 3600,
+        "tracker": "https://tracker.example.org/announce",
+        "tags": "linux, iso",
+        "category": "os",
+        "state": "stalledUP",
+    }))
+    .unwrap();
+
+    let query = TorrentQuery::field(TorrentSortKey::SeedingTime)
+        .gt(30 * 24 * 3600)
+        .and(TorrentQuery::field(TorrentSortKey::Ratio).gt(2))
+        .and(TorrentQuery::field(TorrentSortKey::Tracker).contains("example.org"))
+        .and(!TorrentQuery::tag("keep"));
+    assert!(query.matches(&torrent));
+    assert!(!query.clone().and(TorrentQuery::tag("")).matches(&torrent));
+    #[cfg(feature = "regex")]
+    assert!(TorrentQuery::name_matches(Regex::new(r"(?i)^ubuntu \d+").unwrap()).matches(&torrent));
+    assert!(TorrentQuery::hashes(["abcdef"]).matches(&torrent));
+    assert!(TorrentQuery::filter(TorrentFilter::Stalled).matches(&torrent));
+    assert!(!TorrentQuery::field(TorrentSortKey::Dlspeed)
+        .ge(0)
+        .matches(&torrent));
+    assert!(!TorrentQuery::field(TorrentSortKey::Name)
+        .gt(1)
+        .matches(&torrent));
+
+    let (arg, residual) = query
+        .and(TorrentQuery::category("os"))
+        .and(TorrentQuery::tag("linux"))
+        .split();
+    assert_eq!(arg.category.as_deref(), Some("os"));
+    assert_eq!(arg.tag.as_deref(), Some("linux"));
+    assert!(matches!(residual, TorrentQuery::And(ref q) if q.len() == 4));
+    assert!(residual.matches(&torrent));
+
+    let (arg, residual) = TorrentQuery::hashes(Vec::<String>::new()).split();
+    assert_eq!(arg.hashes, None);
+    assert!(!residual.matches(&torrent));
+}
@@ -0,0 +1,161 @@
+//! Retry policy used by every request sent through [`Qbit`](crate::Qbit).
+
+use std::time::Duration;
+
+use reqwest::StatusCode;
+
+use crate::Error;
+
+/// Endpoints that must not be sent twice, since repeating them changes the
+/// outcome, or fails because the first request already succeeded.
+const NON_IDEMPOTENT: &[&str] = &[
+    "app/shutdown",
+    "rss/addFeed",
+    "rss/addFolder",
+    "torrents/add",
+    "torrents/addPeers",
+    "torrents/addTrackers",
+    "torrents/bottomPrio",
+    "torrents/decreasePrio",
+    "torrents/increasePrio",
+    "torrents/renameFile",
+    "torrents/renameFolder",
+    "torrents/toggleFirstLastPiecePrio",
+    "torrents/toggleSequentialDownload",
+    "torrents/topPrio",
+    "transfer/toggleSpeedLimitsMode",
+];
+
+/// Controls how failed requests are retried. Set with
+/// [`QbitBuilder::retry_policy`](crate::QbitBuilder::retry_policy).
+///
+/// Logging in again after the session expired (`403`) happens at most once per
+/// request and does not count towards `max_attempts`. The login request itself
+/// only retries connection failures.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry
+    pub initial_backoff: Duration,
+    /// Upper bound of the delay between two attempts
+    pub max_backoff: Duration,
+    /// Randomize each delay between zero and the computed backoff
+    pub jitter: bool,
+    /// Retry when the server responds with one of these status codes
+    pub retry_status: Vec<StatusCode>,
+    /// Retry when the connection could not be established. Always safe, since
+    /// the request never reached the server.
+    pub retry_connect: bool,
+    /// Retry on timeouts and other errors after the request was sent
+    pub retry_timeout: bool,
+    /// Also retry requests that are not idempotent, e.g. `torrents/add` or
+    /// `transfer/toggleSpeedLimitsMode`, when they may already have reached
+    /// the server
+    pub retry_non_idempotent: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(10),
+            jitter: true,
+            retry_status: vec![
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            retry_connect: true,
+            retry_timeout: true,
+            retry_non_idempotent: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Only re-login on expired sessions, never retry other failures
+    pub fn none() -> Self {
+        Self {
+            retry_status: vec![],
+            retry_connect: false,
+            retry_timeout: false,
+            ..Self::default()
+        }
+    }
+
+    /// Delay before the `attempt`-th retry, starting from `1`
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff);
+
+        if self.jitter {
+            backoff.mul_f64(fastrand::f64())
+        } else {
+            backoff
+        }
+    }
+
+    /// Whether a request that failed with `err` is retried under this
+    /// policy, if attempts are left. Non-idempotent requests are recognized by
+    /// the path in [`Error::context`].
+    pub fn should_retry(&self, err: &Error) -> bool {
+        let path = err.context().map_or("", |c| c.path);
+        match err {
+            Error::HttpError { error, .. } => self.should_retry_error(error, path),
+            Error::UnknownHttpCode { status, .. } => self.should_retry_status(*status, path),
+            Error::BatchFailed { failures, .. } => {
+                failures.iter().all(|f| self.should_retry(&f.error))
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn should_retry_error(&self, err: &reqwest::Error, path: &str) -> bool {
+        if err.is_connect() {
+            self.retry_connect
+        } else if err.is_timeout() || err.is_request() {
+            self.retry_timeout && self.is_idempotent(path)
+        } else {
+            false
+        }
+    }
+
+    pub(crate) fn should_retry_status(&self, status: StatusCode, path: &str) -> bool {
+        self.retry_status.contains(&status) && self.is_idempotent(path)
+    }
+
+    fn is_idempotent(&self, path: &str) -> bool {
+        self.retry_non_idempotent || !NON_IDEMPOTENT.contains(&path)
+    }
+}
+
+#[test]
+fn test_retry_policy() {
+    let policy = RetryPolicy {
+        jitter: false,
+        initial_backoff: Duration::from_millis(100),
+        max_backoff: Duration::from_millis(300),
+        ..RetryPolicy::default()
+    };
+    assert_eq!(policy.backoff(1), Duration::from_millis(100));
+    assert_eq!(policy.backoff(2), Duration::from_millis(200));
+    assert_eq!(policy.backoff(3), Duration::from_millis(300));
+    assert_eq!(policy.backoff(40), Duration::from_millis(300));
+
+    let jittered = RetryPolicy::default().backoff(2);
+    assert!(jittered <= Duration::from_millis(400));
+
+    let status = StatusCode::BAD_GATEWAY;
+    assert!(policy.should_retry_status(status, "torrents/info"));
+    assert!(policy.should_retry_status(status, "torrents/pause"));
+    assert!(!policy.should_retry_status(status, "torrents/add"));
+    assert!(!policy.should_retry_status(status, "transfer/toggleSpeedLimitsMode"));
+    assert!(!policy.should_retry_status(status, "torrents/renameFile"));
+    assert!(!policy.should_retry_status(StatusCode::NOT_FOUND, "torrents/info"));
+    assert!(!RetryPolicy::none().should_retry_status(status, "torrents/info"));
+}
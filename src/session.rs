@@ -0,0 +1,206 @@
+//! Persistent storage of session cookies.
+//!
+//! By default the cookie obtained by [`Qbit::login`] only lives in memory, so
+//! every short-lived process has to log in again. A [`SessionStore`] set with
+//! [`QbitBuilder::session_store`] is consulted before logging in, updated after
+//! each successful login and cleared when the server rejects the cookie.
+//!
+//! [`Qbit::login`]: crate::Qbit::login
+//! [`QbitBuilder::session_store`]: crate::QbitBuilder::session_store
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use url::Url;
+
+/// Identifies a session: one per endpoint and username
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SessionKey {
+    pub endpoint: String,
+    pub username: String,
+}
+
+impl SessionKey {
+    pub fn new(endpoint: &Url, username: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            username: username.into(),
+        }
+    }
+}
+
+impl Display for SessionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}@{}", self.username, self.endpoint)
+    }
+}
+
+/// Storage for session cookies. Errors are logged and otherwise ignored, i.e.
+/// a failing store behaves like an empty one.
+pub trait SessionStore: Send + Sync {
+    /// Load the cookie stored for `key`
+    fn load(&self, key: &SessionKey) -> io::Result<Option<String>>;
+
+    /// Store `cookie` for `key`, replacing the previous one
+    fn save(&self, key: &SessionKey, cookie: &str) -> io::Result<()>;
+
+    /// Remove the cookie stored for `key`
+    fn remove(&self, key: &SessionKey) -> io::Result<()>;
+}
+
+impl<T: SessionStore + ?Sized> SessionStore for Arc<T> {
+    fn load(&self, key: &SessionKey) -> io::Result<Option<String>> {
+        (**self).load(key)
+    }
+
+    fn save(&self, key: &SessionKey, cookie: &str) -> io::Result<()> {
+        (**self).save(key, cookie)
+    }
+
+    fn remove(&self, key: &SessionKey) -> io::Result<()> {
+        (**self).remove(key)
+    }
+}
+
+/// Session store kept in memory, e.g. to share sessions between several
+/// [`Qbit`](crate::Qbit) instances of the same process
+#[derive(Debug, Default)]
+pub struct MemorySessionStore {
+    sessions: Mutex<HashMap<SessionKey, String>>,
+}
+
+impl MemorySessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SessionStore for MemorySessionStore {
+    fn load(&self, key: &SessionKey) -> io::Result<Option<String>> {
+        Ok(self
+            .sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(key)
+            .cloned())
+    }
+
+    fn save(&self, key: &SessionKey, cookie: &str) -> io::Result<()> {
+        self.sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(key.clone(), cookie.to_owned());
+        Ok(())
+    }
+
+    fn remove(&self, key: &SessionKey) -> io::Result<()> {
+        self.sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(key);
+        Ok(())
+    }
+}
+
+/// Session store backed by a JSON file, mapping `username@endpoint` to the
+/// cookie. On unix, the file is only readable and writable by its owner.
+#[derive(Debug)]
+pub struct FileSessionStore {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl FileSessionStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn read(&self) -> io::Result<HashMap<String, String>> {
+        match fs::read(&self.path) {
+            Ok(data) => serde_json::from_slice(&data).map_err(Into::into),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn write(&self, sessions: &HashMap<String, String>) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("tmp");
+        // A leftover temporary file would keep its permissions
+        match fs::remove_file(&tmp) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(&tmp)?;
+        file.write_all(&serde_json::to_vec_pretty(sessions)?)?;
+        file.sync_all()?;
+        fs::rename(tmp, &self.path)
+    }
+
+    fn update(&self, f: impl FnOnce(&mut HashMap<String, String>)) -> io::Result<()> {
+        let _guard = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut sessions = self.read()?;
+        f(&mut sessions);
+        self.write(&sessions)
+    }
+}
+
+impl SessionStore for FileSessionStore {
+    fn load(&self, key: &SessionKey) -> io::Result<Option<String>> {
+        let _guard = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+        Ok(self.read()?.remove(&key.to_string()))
+    }
+
+    fn save(&self, key: &SessionKey, cookie: &str) -> io::Result<()> {
+        self.update(|sessions| {
+            sessions.insert(key.to_string(), cookie.to_owned());
+        })
+    }
+
+    fn remove(&self, key: &SessionKey) -> io::Result<()> {
+        self.update(|sessions| {
+            sessions.remove(&key.to_string());
+        })
+    }
+}
+
+#[test]
+fn test_file_session_store() {
+    let path = std::env::temp_dir().join(format!("qbit-session-{}.json", std::process::id()));
+    let store = FileSessionStore::new(&path);
+    let key = SessionKey::new(&"http://localhost:8080".parse().unwrap(), "admin");
+    let other = SessionKey::new(&"http://localhost:8081".parse().unwrap(), "admin");
+
+    assert_eq!(store.load(&key).unwrap(), None);
+    store.save(&key, "SID=1").unwrap();
+    store.save(&other, "SID=2").unwrap();
+    assert_eq!(store.load(&key).unwrap().as_deref(), Some("SID=1"));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    let reopened = FileSessionStore::new(&path);
+    assert_eq!(reopened.load(&other).unwrap().as_deref(), Some("SID=2"));
+    reopened.remove(&key).unwrap();
+    assert_eq!(store.load(&key).unwrap(), None);
+
+    fs::remove_file(path).unwrap();
+}